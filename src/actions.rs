@@ -1,36 +1,356 @@
-use std::{fs, io::stdin, path::PathBuf};
+use std::{
+    collections::BTreeSet,
+    ffi::{OsStr, OsString},
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
 
 use anyhow::{Context, Result};
-use indicatif::{ProgressBar, ProgressStyle};
+use comfy_table::{presets::UTF8_FULL_CONDENSED, ContentArrangement, Table};
 use jiff::Zoned;
+use serde::Serialize;
+
+use crate::{
+    dates::format_age,
+    display::{
+        confirm, confirm_each_item, confirm_typing, progress_bar, EachAnswer, PercentProgress,
+    },
+    error,
+    errors::TrasherError,
+    fuzzy::{compute_fuzzy_find_score, find_match, is_case_sensitive, FuzzyFinderItem},
+    info, success, warn,
+};
+
+use super::{
+    args::*, bail, debug, fsutils::*, items::*, pending::*, policy::*, signals::*, system_trash::*,
+};
+
+pub fn list(action: ListTrashItems, config: &Config) -> Result<ExitCode> {
+    let ListTrashItems {
+        name,
+        show_owner,
+        system,
+        check,
+        empty_ok,
+        grouped,
+        json,
+        output,
+        force,
+        newest,
+        oldest,
+        since,
+        until,
+        count,
+        tag,
+        min_size,
+        max_size,
+        show_tags,
+        show_host,
+        show_original_path,
+        path_depth,
+        trash_dir,
+        stream,
+        expand,
+        expand_depth,
+    } = action;
+
+    if system {
+        list_system_trash(name.as_deref())?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let trash_dirs = resolve_trash_dirs_scope(&trash_dir, config)?;
+
+    if grouped {
+        let options = TableOptions {
+            owner: show_owner,
+            tags: show_tags,
+            host: show_host,
+            original_path: show_original_path,
+            path_depth,
+            size_format: config.size_format,
+            scores: None,
+        };
+
+        list_grouped(name.as_deref(), &trash_dirs, &options, config)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let case_sensitive = name
+        .as_deref()
+        .is_some_and(|name| is_case_sensitive(name, config.ignore_case));
+
+    let matches_filters = |trashed: &TrashedItem| {
+        if let Some(name) = &name {
+            if find_match(&trashed.data.filename_lossy(), name, case_sensitive).is_none() {
+                return false;
+            }
+        }
+
+        if !tag
+            .iter()
+            .all(|tag| trashed.data.metadata.tags.contains(tag))
+        {
+            return false;
+        }
+
+        if since.is_some_and(|since| trashed.data.datetime < since)
+            || until.is_some_and(|until| trashed.data.datetime > until)
+        {
+            return false;
+        }
+
+        if min_size.is_none() && max_size.is_none() {
+            return true;
+        }
+
+        let path = trashed.complete_trash_item_path();
+
+        match item_size(&path) {
+            Ok(size) => {
+                min_size.is_none_or(|min_size| size >= min_size)
+                    && max_size.is_none_or(|max_size| size <= max_size)
+            }
+            Err(err) => {
+                warn!(
+                    "Failed to compute size of item '{}': {err:?}",
+                    path.display()
+                );
+                false
+            }
+        }
+    };
+
+    // Items don't need to be sorted, so handle them one by one without keeping them in memory
+    if stream || (count && newest.is_none() && oldest.is_none()) {
+        let trash_dirs = if trash_dirs.is_empty() {
+            list_trash_dirs(config)?.into_iter().collect()
+        } else {
+            trash_dirs
+        };
+
+        let mut matching = 0;
+        let mut stdout = io::stdout().lock();
+
+        for trash_dir in &trash_dirs {
+            for trashed in iter_trash_items(trash_dir, config)? {
+                let trashed = trashed?;
+
+                if !matches_filters(&trashed) {
+                    continue;
+                }
+
+                if stream {
+                    let line = serde_json::to_string(&ItemDescription::new(&trashed))
+                        .context("Failed to serialize item")?;
+
+                    match writeln!(stdout, "{line}") {
+                        Ok(()) => {}
+                        // The reader may stop early (e.g. when piped to 'head')
+                        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => {
+                            return Ok(ExitCode::SUCCESS)
+                        }
+                        Err(err) => return Err(err).context("Failed to print item"),
+                    }
+                }
 
-use crate::{fuzzy::FuzzyFinderItem, info, success, warn};
+                matching += 1;
+            }
+        }
 
-use super::{args::*, bail, debug, fsutils::*, items::*};
+        if count {
+            println!("{matching}");
+        }
 
-pub fn list(action: ListTrashItems, config: &Config) -> Result<()> {
-    let ListTrashItems { name } = action;
+        return Ok(ExitCode::SUCCESS);
+    }
 
     debug!("Listing trash items...");
 
-    let mut items = list_all_trash_items(config)?;
+    let mut items = if trash_dirs.is_empty() {
+        list_all_trash_items(config)?
+    } else {
+        list_trash_items_of(&trash_dirs, config)?
+    };
 
-    if items.is_empty() {
-        info!("All trashes are empty.");
-        return Ok(());
+    let total_items = items.len();
+
+    debug!("Filtering {} items...", items.len());
+
+    items.retain(|trashed| matches_filters(trashed));
+
+    // Items are sorted by deletion date
+    if let Some(newest) = newest {
+        items.drain(..items.len().saturating_sub(newest));
+    }
+
+    if let Some(oldest) = oldest {
+        items.truncate(oldest);
+    }
+
+    if check {
+        return Ok(if items.is_empty() == empty_ok {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        });
+    }
+
+    if count {
+        println!("{}", items.len());
+        return Ok(ExitCode::SUCCESS);
     }
 
-    if let Some(name) = &name {
-        debug!("Filtering {} items by name...", items.len());
-        items.retain(|trashed| trashed.data.filename.contains(name));
+    if !json {
+        if total_items == 0 {
+            info!("All trashes are empty.");
+            return Ok(ExitCode::SUCCESS);
+        }
 
         if items.is_empty() {
             info!("No item in trash match the provided name.");
-            return Ok(());
+            return Ok(ExitCode::SUCCESS);
+        }
+    }
+
+    let rendered = if json {
+        json_for_items(&items)?
+    } else {
+        let options = TableOptions {
+            owner: show_owner,
+            tags: show_tags,
+            host: show_host,
+            original_path: show_original_path,
+            path_depth,
+            size_format: config.size_format,
+            scores: None,
+        };
+
+        let mut rendered = table_for_items(&items, &options).to_string();
+
+        if expand {
+            for item in &items {
+                let path = item.complete_trash_item_path();
+
+                if !is_real_dir(&path) {
+                    continue;
+                }
+
+                rendered.push_str(&format!(
+                    "\n\n{} ({}):\n{}",
+                    item.data.filename_lossy(),
+                    item.data.compute_id(),
+                    render_dir_tree(&path, expand_depth)?
+                ));
+            }
+        }
+
+        rendered
+    };
+
+    match output {
+        Some(output) => write_output_file(&output, &rendered, force)?,
+        None => println!("{rendered}"),
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Write a command's output to a file, creating its parent directories if needed
+fn write_output_file(path: &Path, content: &str, force: bool) -> Result<()> {
+    if path.exists() && !force {
+        bail!(TrasherError::TargetExists(path.to_path_buf()));
+    }
+
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+
+    fs::write(path, format!("{content}\n"))
+        .with_context(|| format!("Failed to write output file: {}", path.display()))
+}
+
+fn list_grouped(
+    name: Option<&str>,
+    trash_dirs: &[PathBuf],
+    options: &TableOptions,
+    config: &Config,
+) -> Result<()> {
+    let mut total_items = 0;
+    let mut total_size = 0;
+
+    let trash_dirs = if trash_dirs.is_empty() {
+        list_trash_dirs(config)?.into_iter().collect()
+    } else {
+        trash_dirs.to_vec()
+    };
+
+    for trash_dir in trash_dirs {
+        let mut items = list_trash_items(&trash_dir, config)?;
+
+        if let Some(name) = name {
+            let case_sensitive = is_case_sensitive(name, config.ignore_case);
+
+            items.retain(|trashed| {
+                find_match(&trashed.data.filename_lossy(), name, case_sensitive).is_some()
+            });
+        }
+
+        let fstype = filesystem_type(&trash_dir).unwrap_or_else(|| "unknown".to_owned());
+
+        if items.is_empty() {
+            info!(
+                "Trash directory: {} [{fstype}] (empty)",
+                trash_dir.display()
+            );
+            continue;
+        }
+
+        items.sort_by_key(|item| item.data.datetime);
+
+        let mut size = 0;
+
+        for item in &items {
+            let path = item.complete_trash_item_path();
+
+            match item_size(&path) {
+                Ok(item_size) => size += item_size,
+                Err(err) => warn!(
+                    "Failed to compute size of item '{}': {err:?}",
+                    path.display()
+                ),
+            }
         }
+
+        info!(
+            "Trash directory: {} [{fstype}] ({} item(s), {})",
+            trash_dir.display(),
+            items.len(),
+            human_readable_size(size, config.size_format)
+        );
+
+        println!("{}", table_for_items(&items, options));
+
+        total_items += items.len();
+        total_size += size;
     }
 
-    println!("{}", table_for_items(&items));
+    info!(
+        "Total: {total_items} item(s), {}",
+        human_readable_size(total_size, config.size_format)
+    );
 
     Ok(())
 }
@@ -41,22 +361,76 @@ pub fn remove(action: MoveToTrash, config: &Config) -> Result<()> {
         permanently,
         ignore,
         allow_invalid_utf8_item_names,
+        system,
+        no_network_trash,
+        to_trash_dir,
+        create_trash_dir,
+        keep_copy,
+        move_ext_filesystems,
+        size_limit_move_ext_filesystems,
+        sync,
+        delay,
+        contents_only,
+        one_file_system,
+        as_name,
+        tag,
+        json,
+        quiet,
     } = action;
 
+    let paths = if contents_only {
+        list_directories_contents(paths)?
+    } else {
+        paths
+    };
+
+    if as_name.is_some() && paths.len() > 1 {
+        bail!(
+            "A custom name can only be provided with --as when moving a single item to the trash"
+        );
+    }
+
     debug!("Going to remove {} item(s)...", paths.len());
 
+    let mut pending_deletions = vec![];
+
+    let to_trash_dir = to_trash_dir
+        .map(|trash_dir| prepare_trash_dir_override(&trash_dir, create_trash_dir, config))
+        .transpose()?;
+
+    let mut system_trash_paths = vec![];
+    let mut reports = vec![];
+
+    // The summary isn't displayed alongside the JSON output
+    let summary = !quiet && !json;
+    let mut trashed = 0;
+    // Only known if no directory was renamed, as computing their size would require walking through them
+    let mut trashed_size = Some(0);
+    let mut used_trash_dirs = BTreeSet::new();
+
     for (i, path) in paths.iter().enumerate() {
         debug!("Treating item {} on {}...", i + 1, paths.len());
 
-        let path = PathBuf::from(path);
-
         debug!("Checking if item exists...");
 
-        if is_dangerous_path(&path) {
+        // Paths like '.' or '..' don't have a file name, so use the directory they point to
+        let resolved_path;
+
+        let path = if path.file_name().is_none() && fs::symlink_metadata(path).is_ok() {
+            resolved_path = fs::canonicalize(path)
+                .with_context(|| format!("Failed to canonicalize path: {}", path.display()))?;
+
+            &resolved_path
+        } else {
+            path
+        };
+
+        if is_dangerous_path(path) {
             bail!("Removing this path is too dangerous, operation aborted.");
         }
 
-        if !path.exists() {
+        // Don't follow symbolic links so broken ones can be trashed too
+        if fs::symlink_metadata(path).is_err() {
             if ignore {
                 continue;
             }
@@ -64,14 +438,58 @@ pub fn remove(action: MoveToTrash, config: &Config) -> Result<()> {
             bail!("No item exists at path: {}", path.display());
         }
 
-        if permanently {
-            let deletion_result = if path.is_file() {
-                fs::remove_file(&path)
-            } else {
-                fs::remove_dir_all(&path)
-            };
+        if contains_current_dir(path)? {
+            let question = format!(
+                "Directory '{}' is or contains the current directory, you will need to move out of it afterwards.\nAre you sure you want to remove it",
+                path.display()
+            );
+
+            if !confirm(&question, config)? {
+                warn!("Skipped item: {}", path.display());
+                continue;
+            }
+        }
+
+        if one_file_system && is_real_dir(path) {
+            if let Some(nested) = find_nested_mount_point(path)? {
+                bail!(
+                    "Directory '{}' contains an item located on another filesystem: {}\n\nRefusing to move it because of --one-file-system.",
+                    path.display(),
+                    nested.display()
+                );
+            }
+        }
+
+        if system {
+            system_trash_paths.push(path.clone());
+            continue;
+        }
+
+        if let Some(delay) = delay {
+            let original_path = absolute_item_path(path)?;
+            let pending_path = pending_path_for(&original_path)?;
+
+            if fs::symlink_metadata(&pending_path).is_ok() {
+                bail!(
+                    "Deletion of an item with the same name is already pending: {}",
+                    pending_path.display()
+                );
+            }
+
+            fs::rename(&original_path, &pending_path)
+                .with_context(|| format!("Failed to hide item '{}'", original_path.display()))?;
+
+            pending_deletions.push(PendingDeletion::new(
+                original_path,
+                pending_path,
+                delay,
+                tag.clone(),
+            ));
+            continue;
+        }
 
-            match deletion_result {
+        if permanently {
+            match remove_item(path) {
                 Err(err) => bail!("Failed to permanently remove item: {}", err),
                 Ok(()) => continue,
             }
@@ -81,41 +499,79 @@ pub fn remove(action: MoveToTrash, config: &Config) -> Result<()> {
             .file_name()
             .context("Specified item path has no file name")?;
 
-        let filename = match filename.to_str() {
-            Some(str) => str.to_string(),
+        if filename.to_str().is_none() && !allow_invalid_utf8_item_names {
+            bail!("Specified item does not have a valid UTF-8 file name")
+        }
+
+        let filename = match &as_name {
+            Some(name) => OsStr::new(name),
+            None => filename,
+        };
+
+        let mut data = TrashItemInfos::new_now(filename.to_owned());
+        data.metadata.owner = ItemOwner::current();
+        data.metadata.tags = tag.clone();
+        data.metadata.hostname = TrashItemMetadata::current_hostname();
+        data.metadata.attributes = fs::symlink_metadata(path)
+            .ok()
+            .and_then(|mt| ItemAttributes::of(&mt));
+
+        debug!(
+            "Moving item to trash under name '{}'...",
+            data.trash_filename().to_string_lossy()
+        );
+
+        let trash_dir = match &to_trash_dir {
+            Some(trash_dir) => trash_dir.clone(),
             None => {
-                if allow_invalid_utf8_item_names {
-                    filename.to_string_lossy().to_string()
+                let trash_dir = determine_effective_trash_dir_for(path, no_network_trash, config)?;
+
+                if move_ext_filesystems {
+                    route_ext_filesystem_item(
+                        path,
+                        trash_dir,
+                        size_limit_move_ext_filesystems,
+                        config,
+                    )?
                 } else {
-                    bail!("Specified item does not have a valid UTF-8 file name")
+                    trash_dir
                 }
             }
         };
 
-        let data = TrashItemInfos::new_now(filename.to_string());
+        let original_path = absolute_item_path(path)?;
+        data.metadata.original_path = Some(original_path.clone());
 
         debug!(
-            "Moving item to trash under name '{}'...",
-            data.trash_filename()
+            "Trashing {} -> {}",
+            original_path.display(),
+            trash_dir.display()
         );
 
-        let trash_dir = determine_trash_dir_for(&path, config).with_context(|| {
-            format!(
-                "Failed to determine path to the trash directory for item: {}",
-                path.display()
-            )
-        })?;
+        if trash_dir.starts_with(&original_path) {
+            bail!(
+                "Cannot move item '{}' to the trash as it is or contains the trash directory ({})",
+                original_path.display(),
+                trash_dir.display()
+            );
+        }
+
+        ensure_trash_dir_is_dir(&trash_dir)?;
 
         if !trash_dir.exists() {
-            fs::create_dir(&trash_dir).with_context(|| {
+            fs::create_dir_all(&trash_dir).with_context(|| {
                 format!(
                     "Failed to create trash directory at path '{}'",
                     trash_dir.display()
                 )
             })?;
+
+            if let Err(err) = write_recovery_note(&trash_dir) {
+                warn!("{err:?}");
+            }
         }
 
-        let trash_transfer_dir = trash_dir.join(TRASH_TRANSFER_DIRNAME);
+        let trash_transfer_dir = trash_dir.join(transfer_dir_name(config));
 
         if !trash_transfer_dir.exists() {
             fs::create_dir(&trash_transfer_dir).with_context(|| {
@@ -126,21 +582,94 @@ pub fn remove(action: MoveToTrash, config: &Config) -> Result<()> {
             })?;
         }
 
-        if !are_on_same_fs(&path, &trash_dir)? {
-            info!("Moving item to trash directory {}", trash_dir.display());
+        let trash_item = TrashedItem {
+            data,
+            trash_dir,
+            pending_in: None,
+        };
+
+        // Metadata is written first, so an interrupted transfer can be told apart from a complete one
+        // using the original path (see `TrashedItem::is_known_complete`)
+        trash_item.write_metadata(sync)?;
+
+        let same_fs = are_on_same_fs(path, &trash_item.trash_dir)?;
+
+        debug!(
+            "Item will be {} trash directory: {}",
+            if same_fs && !keep_copy {
+                "renamed into the"
+            } else {
+                "copied to the"
+            },
+            trash_item.trash_dir.display()
+        );
+
+        if keep_copy || !same_fs {
+            // Don't mix messages with the JSON output
+            if !json {
+                let action = if keep_copy { "Copying" } else { "Moving" };
+
+                info!(
+                    "{action} item to trash directory {}",
+                    trash_item.trash_dir.display()
+                );
+            }
+
+            let transfer_path = trash_item.transfer_trash_item_path(config);
+
+            // The item is first copied entirely, then removed from its original location,
+            // so a failed (or interrupted) transfer leaves the source intact
+            let transfer = PartialTransferGuard::track(&transfer_path, config);
+
+            let copied = match copy_item_with_retries(path, &transfer_path, config.retries) {
+                Ok(copied) => copied,
+                Err(err) => {
+                    if !config.no_cleanup {
+                        cleanup_partial_transfer(&transfer_path);
+                        discard_metadata_after_failure(&trash_item);
+                    }
+
+                    return Err(err.context("Failed to move item to the trash"));
+                }
+            };
+
+            trashed_size = trashed_size.map(|size| size + copied);
 
-            let transfer_path = trash_transfer_dir.join(data.trash_filename());
+            transfer.complete();
+
+            if sync {
+                sync_path(&transfer_path)?;
+                sync_path(&trash_transfer_dir)?;
+            }
 
-            move_item_pbr(&path, &transfer_path).context("Failed to move item to the trash")?;
+            if !keep_copy {
+                remove_item(path)
+                    .with_context(|| format!("Failed to remove moved item '{}'", path.display()))?;
+            }
 
-            fs::rename(&transfer_path, trash_dir.join(data.trash_filename()))
+            fs::rename(&transfer_path, trash_item.complete_trash_item_path())
                 .context("Failed to move item to the final trash directory")?;
         } else {
-            let trash_item = TrashedItem { data, trash_dir };
-            let trash_item_path = trash_item.transfer_trash_item_path();
+            let trash_item_path = trash_item.transfer_trash_item_path(config);
+
+            // Getting the size of a file is cheap, unlike a directory's
+            let size = fs::symlink_metadata(path)
+                .ok()
+                .filter(|mt| !mt.is_dir())
+                .map(|mt| mt.len());
+
+            if let Err(err) = fs::rename(path, &trash_item_path) {
+                discard_metadata_after_failure(&trash_item);
+
+                return Err(err)
+                    .with_context(|| format!("Failed to move item '{}' to trash", path.display()));
+            }
+
+            trashed_size = trashed_size.zip(size).map(|(total, size)| total + size);
 
-            fs::rename(&path, &trash_item_path)
-                .with_context(|| format!("Failed to move item '{}' to trash", path.display()))?;
+            if sync {
+                sync_path(&trash_transfer_dir)?;
+            }
 
             fs::rename(&trash_item_path, trash_item.complete_trash_item_path()).with_context(
                 || {
@@ -151,229 +680,2367 @@ pub fn remove(action: MoveToTrash, config: &Config) -> Result<()> {
                 },
             )?;
         }
-    }
-
-    Ok(())
-}
-
-pub fn drop(action: DropItem, config: &Config) -> Result<()> {
-    let DropItem { filename, id } = action;
-
-    debug!("Listing trash items...");
 
-    let item = expect_single_trash_item(&filename, id.as_deref(), config)?;
+        if sync {
+            sync_path(&trash_item.trash_dir)?;
+            sync_path(&trash_transfer_dir)?;
+        }
 
-    debug!("Permanently removing item from trash...");
+        trashed += 1;
+        used_trash_dirs.insert(trash_item.trash_dir.clone());
+
+        if json {
+            reports.push(TrashingReport {
+                original_path,
+                trash_path: trash_item.complete_trash_item_path(),
+                id: trash_item.data.compute_id(),
+                method: if keep_copy {
+                    "copy"
+                } else if same_fs {
+                    "rename"
+                } else {
+                    "cross_fs_move"
+                },
+                trash_dir: trash_item.trash_dir,
+            });
+        }
+    }
 
-    let path = item.complete_trash_item_path();
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&reports).context("Failed to serialize report")?
+        );
+    }
 
-    let result = if path.is_dir() {
-        fs::remove_dir_all(path)
-    } else {
-        fs::remove_file(path)
-    };
+    if !pending_deletions.is_empty() {
+        let count = pending_deletions.len();
 
-    result.with_context(|| format!("Failed to remove item '{}' from trash", item.data.filename))
-}
+        let mut all_pending = load_pending_deletions()?;
+        all_pending.extend(pending_deletions);
+        save_pending_deletions(&all_pending)?;
 
-pub fn path_of(action: GetItemPath, config: &Config) -> Result<()> {
-    let GetItemPath {
-        filename,
-        id,
-        allow_invalid_utf8_path,
-    } = action;
+        info!(
+            "{count} item(s) will be moved to the trash by 'trasher commit' after the delay, use 'trasher abort' to cancel."
+        );
+    }
 
-    debug!("Listing trash items...");
+    if !system_trash_paths.is_empty() {
+        debug!(
+            "Moving {} item(s) to the system trash...",
+            system_trash_paths.len()
+        );
 
-    let item = expect_single_trash_item(&filename, id.as_deref(), config)?;
-    let item_path = item.complete_trash_item_path();
+        move_to_system_trash(&system_trash_paths)?;
+    }
 
-    match item_path.to_str() {
-        Some(path) => println!("{}", path),
-        None => {
-            if allow_invalid_utf8_path {
-                println!("{}", item_path.to_string_lossy())
-            } else {
-                bail!(
-                    "Path contains invalid UTF-8 characters (lossy: {})",
-                    item_path.to_string_lossy()
-                );
-            }
-        }
+    if summary && trashed > 0 {
+        success!(
+            "{}",
+            trash_summary(
+                trashed,
+                trashed_size,
+                used_trash_dirs.len(),
+                config.size_format
+            )
+        );
     }
 
     Ok(())
 }
 
-pub fn restore(action: RestoreItem, config: &Config) -> Result<()> {
-    let RestoreItem { filename, to, id } = action;
+/// Describe the items moved to the trash by the [`remove`] command
+fn trash_summary(
+    trashed: usize,
+    size: Option<u64>,
+    trash_dirs: usize,
+    size_format: SizeFormat,
+) -> String {
+    let size = match size {
+        Some(size) => format!(" ({} total)", human_readable_size(size, size_format)),
+        None => String::new(),
+    };
 
-    debug!("Listing trash items...");
+    format!("Trashed {trashed} item(s){size} to {trash_dirs} trash director(ies).")
+}
 
-    let Some(filename) = filename else {
-        return restore_with_ui(config);
-    };
+/// Description of an item moved to the trash, displayed by `rm --json`
+#[derive(Serialize)]
+struct TrashingReport {
+    original_path: PathBuf,
+    trash_path: PathBuf,
+    trash_dir: PathBuf,
+    id: String,
+    method: &'static str,
+}
 
-    let item = expect_single_trash_item(&filename, id.as_deref(), config)?;
+/// Replace directories by their immediate children, leaving other paths as they are
+fn list_directories_contents(paths: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+    let mut contents = vec![];
 
-    let item_path = item.complete_trash_item_path();
+    for path in paths {
+        let is_dir = is_real_dir(&path);
 
-    let target_path = match to {
-        Some(to) => to,
-        None => std::env::current_dir()?,
-    };
+        if !is_dir {
+            contents.push(path);
+            continue;
+        }
 
-    let target_path = target_path.join(&item.data.filename);
+        if is_dangerous_path(&absolute_item_path(&path)?) {
+            bail!("Removing the content of this directory is too dangerous, operation aborted.");
+        }
 
-    if target_path.exists() {
-        bail!("Target path already exists.");
-    }
+        let mut children = fs::read_dir(&path)
+            .with_context(|| format!("Failed to read directory: {}", path.display()))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("Failed to read directory entry in: {}", path.display()))?;
 
-    let target_parent = target_path.parent().unwrap();
+        // Trash directories can't be moved to the trash
+        children.retain(|child| !is_trash_dir_path(child));
+        children.sort();
 
-    if !target_parent.exists() {
-        bail!(
-            "Target directory '{}' does not exist",
-            target_parent.display()
+        debug!(
+            "Directory '{}' contains {} item(s) to remove",
+            path.display(),
+            children.len()
         );
-    }
 
-    let result = if are_on_same_fs(&item.complete_trash_item_path(), target_parent)? {
-        debug!("Restoring item from trash...");
+        contents.extend(children);
+    }
 
-        fs::rename(item_path, &target_path).context("Rename operation failed")
-    } else {
-        info!("Moving file across filesystems...");
+    Ok(contents)
+}
 
-        move_item_pbr(&item_path, &target_path)
-    };
+/// Determine the trash directory to move an item to, avoiding network filesystems if asked to
+fn determine_effective_trash_dir_for(
+    path: &Path,
+    no_network_trash: bool,
+    config: &Config,
+) -> Result<PathBuf> {
+    let trash_dir = determine_trash_dir_for(path, config).with_context(|| {
+        format!(
+            "Failed to determine path to the trash directory for item: {}",
+            path.display()
+        )
+    })?;
+
+    let trash_dir_fstype = filesystem_type(trash_dir.parent().unwrap_or(&trash_dir))
+        .filter(|fstype| is_network_filesystem(fstype));
+
+    Ok(match trash_dir_fstype {
+        Some(fstype) if no_network_trash => {
+            debug!(
+                "Trash directory '{}' is on a network filesystem ({}), using the home directory's trash instead",
+                trash_dir.display(),
+                fstype
+            );
+
+            home_trash_dir(config)?
+        }
 
-    result.with_context(|| format!("Failed to restore item '{}' from trash", item.data.filename))
-}
+        Some(fstype) => {
+            if !are_on_same_fs(path, trash_dir.parent().unwrap_or(&trash_dir))? {
+                let size = item_size(path)
+                    .map(|size| human_readable_size(size, config.size_format))
+                    .unwrap_or_else(|_| "<unknown size>".to_owned());
 
-pub fn restore_with_ui(config: &Config) -> Result<()> {
-    let items = list_all_trash_items(config)?;
+                info!(
+                    "Trash directory '{}' is on a network filesystem ({fstype}), moving {size} may take a while.",
+                    trash_dir.display()
+                );
+            }
 
-    if items.is_empty() {
-        info!("Trash is empty");
-        return Ok(());
+            trash_dir
+        }
+
+        None => trash_dir,
+    })
+}
+
+/// Pick the home directory's trash for items located on external filesystems,
+/// unless they are larger than the provided size limit
+fn route_ext_filesystem_item(
+    path: &Path,
+    trash_dir: PathBuf,
+    size_limit: Option<u64>,
+    config: &Config,
+) -> Result<PathBuf> {
+    let home_trash_dir = home_trash_dir(config)?;
+
+    if trash_dir == home_trash_dir {
+        return Ok(trash_dir);
     }
 
-    let to_remove = crate::fuzzy::run_fuzzy_finder(
-        items
-            .into_iter()
-            .map(|item| FuzzyFinderItem {
-                display: format!(
-                    "[{}] {}",
-                    Zoned::try_from(item.data.datetime)
-                        .and_then(|date| jiff::fmt::rfc2822::to_string(&date))
-                        .unwrap_or_else(|_| "<Failed to format date>".to_owned()),
-                    item.data.filename
-                ),
-                value: item,
-            })
-            .collect(),
-    )?;
+    let Some(size_limit) = size_limit else {
+        return Ok(home_trash_dir);
+    };
 
-    restore(
-        RestoreItem {
-            filename: Some(to_remove.data.filename.to_owned()),
-            to: None,
-            id: Some(to_remove.data.compute_id().to_owned()),
-        },
+    match item_size(path) {
+        Ok(size) if size <= size_limit => Ok(home_trash_dir),
+
+        Ok(size) => {
+            debug!(
+                "Item is larger than the size limit ({} > {}), keeping it on its filesystem",
+                human_readable_size(size, config.size_format),
+                human_readable_size(size_limit, config.size_format)
+            );
+
+            Ok(trash_dir)
+        }
+
+        // Avoid copying an item of unknown size across filesystems
+        Err(err) => {
+            warn!(
+                "Failed to compute size of item '{}', keeping it on its filesystem: {err:?}",
+                path.display()
+            );
+
+            Ok(trash_dir)
+        }
+    }
+}
+
+/// Ensure a trash directory provided by the user can be used
+/// Ensure the trash directory provided with `--to-trash-dir` is usable, and return its canonical path
+fn prepare_trash_dir_override(trash_dir: &Path, create: bool, config: &Config) -> Result<PathBuf> {
+    if !trash_dir.exists() {
+        if !create {
+            bail!(
+                "Provided trash directory does not exist: {}\n\nTip: use --create-trash-dir to create it.",
+                trash_dir.display()
+            );
+        }
+
+        fs::create_dir_all(trash_dir).with_context(|| {
+            format!(
+                "Failed to create trash directory at path '{}'",
+                trash_dir.display()
+            )
+        })?;
+
+        if let Err(err) = write_recovery_note(trash_dir) {
+            warn!("{err:?}");
+        }
+    }
+
+    let mt = fs::metadata(trash_dir).with_context(|| {
+        format!(
+            "Failed to get metadata for trash directory: {}",
+            trash_dir.display()
+        )
+    })?;
+
+    if !mt.is_dir() {
+        bail!(
+            "Provided trash directory is not a directory: {}",
+            trash_dir.display()
+        );
+    }
+
+    let trash_dir = fs::canonicalize(trash_dir).with_context(|| {
+        format!(
+            "Failed to canonicalize trash directory path: {}",
+            trash_dir.display()
+        )
+    })?;
+
+    // Creating the transfer directory right away tells if the trash directory is actually writable
+    let trash_transfer_dir = trash_dir.join(transfer_dir_name(config));
+
+    if let Err(err) = fs::create_dir(&trash_transfer_dir) {
+        if err.kind() != io::ErrorKind::AlreadyExists {
+            return Err(err).with_context(|| {
+                format!(
+                    "Provided trash directory is not writable: {}",
+                    trash_dir.display()
+                )
+            });
+        }
+    }
+
+    Ok(trash_dir)
+}
+
+pub fn export(action: ExportItem, config: &Config) -> Result<()> {
+    let ExportItem {
+        filename,
+        id,
+        output,
+        force,
+    } = action;
+
+    let format = ArchiveFormat::from_path(&output)?;
+
+    if fs::symlink_metadata(&output).is_ok() && !force {
+        bail!(TrasherError::TargetExists(output));
+    }
+
+    debug!("Listing trash items...");
+
+    let item = expect_single_trash_item(filename.as_deref(), id.as_deref(), &[], false, config)?;
+
+    info!(
+        "Exporting item '{}' to archive: {}",
+        item.data.filename_lossy(),
+        output.display()
+    );
+
+    if let Err(err) = write_archive(
+        &item.complete_trash_item_path(),
+        Path::new(&item.data.filename),
+        &output,
+        format,
+    ) {
+        if output.exists() && !config.no_cleanup {
+            if let Err(err) = fs::remove_file(&output) {
+                warn!("Failed to remove partially written archive: {err}");
+            }
+        }
+
+        return Err(err.context(format!("Failed to export item to: {}", output.display())));
+    }
+
+    success!("Item was successfully exported.");
+
+    Ok(())
+}
+
+pub fn drop(action: DropItem, config: &Config) -> Result<()> {
+    let DropItem {
+        filename,
+        id,
+        trash_dir,
+        json,
+        older_than,
+        matching,
+        confirm_each,
+    } = action;
+
+    let trash_dirs = resolve_trash_dirs_scope(&trash_dir, config)?;
+
+    if older_than.is_some() || matching.is_some() {
+        return drop_matching(
+            older_than,
+            matching.as_deref(),
+            &trash_dirs,
+            confirm_each,
+            config,
+        );
+    }
+
+    debug!("Listing trash items...");
+
+    let item = expect_single_trash_item(
+        filename.as_deref(),
+        id.as_deref(),
+        &trash_dirs,
+        json,
         config,
     )?;
 
-    Ok(())
+    debug!("Permanently removing item from trash...");
+
+    let path = item.complete_trash_item_path();
+
+    remove_item(&path).with_context(|| {
+        format!(
+            "Failed to remove item '{}' from {}",
+            item.data.filename_lossy(),
+            describe_trash_dir_location(&item.trash_dir)
+        )
+    })?;
+
+    item.discard_metadata()
 }
 
-pub fn empty(config: &Config) -> Result<()> {
-    let trash_dirs = list_trash_dirs(config)?;
-    let items = list_all_trash_items(config)?;
+/// Drop all items trashed before a duration and/or whose name matches, optionally asking for each one
+fn drop_matching(
+    older_than: Option<Duration>,
+    matching: Option<&str>,
+    trash_dirs: &[PathBuf],
+    confirm_each: bool,
+    config: &Config,
+) -> Result<()> {
+    let cutoff = older_than
+        .map(|older_than| {
+            SystemTime::now()
+                .checked_sub(older_than)
+                .context("Provided duration is too large")
+        })
+        .transpose()?;
+
+    let case_sensitive = matching.is_some_and(|name| is_case_sensitive(name, config.ignore_case));
+
+    let mut items = if trash_dirs.is_empty() {
+        list_all_trash_items(config)?
+    } else {
+        list_trash_items_of(trash_dirs, config)?
+    };
+
+    items.retain(|item| {
+        cutoff.is_none_or(|cutoff| item.data.datetime < cutoff)
+            && matching.is_none_or(|name| {
+                find_match(&item.data.filename_lossy(), name, case_sensitive).is_some()
+            })
+    });
 
     if items.is_empty() {
-        info!("Trash is empty");
+        info!("No item to drop.");
         return Ok(());
     }
 
-    warn!("You are about to delete the entire trash directories of:\n");
+    // Oldest items are proposed first
+    items.sort_by_key(|item| item.data.datetime);
 
-    for trash_dir in &trash_dirs {
-        warn!(
-            "  {} ({} items)",
-            trash_dir.display(),
-            items
-                .iter()
-                .filter(|item| &item.trash_dir == trash_dir)
-                .count()
-        );
-    }
+    if !confirm_each {
+        let options = TableOptions {
+            size_format: config.size_format,
+            ..Default::default()
+        };
 
-    warn!("\nAre you sure you want to continue [y/N]?");
+        println!("{}", table_for_items(&items, &options));
 
-    let mut confirm_str = String::new();
+        if !confirm(
+            &format!("Permanently delete {} item(s)", items.len()),
+            config,
+        )? {
+            warn!("Aborted.");
+            return Ok(());
+        }
+    }
 
-    stdin()
-        .read_line(&mut confirm_str)
-        .context("Failed to get user confirmation")?;
+    let mut dropped = 0;
+    let mut drop_all = !confirm_each;
+
+    for item in &items {
+        if !drop_all {
+            let size = item_size(&item.complete_trash_item_path())
+                .map(|size| human_readable_size(size, config.size_format))
+                .unwrap_or_else(|_| "unknown size".to_owned());
+
+            let question = format!(
+                "Permanently delete '{}' ({size}, trashed {})",
+                item.data.filename_lossy(),
+                format_age(item.data.datetime)
+            );
+
+            match confirm_each_item(&question, config)? {
+                EachAnswer::Yes => {}
+                EachAnswer::No => continue,
+                EachAnswer::All => drop_all = true,
+                EachAnswer::Quit => break,
+            }
+        }
 
-    if !confirm_str.trim().eq_ignore_ascii_case("y") {
-        warn!("Cancelled.");
-        return Ok(());
+        purge_item(item)?;
+        dropped += 1;
     }
 
-    info!("Emptying the trash...");
+    success!("Dropped {dropped} item(s) from the trash.");
 
-    for trash_dir in trash_dirs {
-        info!("Emptying trash directory: {}", trash_dir.display());
+    Ok(())
+}
 
-        warn!("> Listing files and directories to delete...");
+pub fn path_of(action: GetItemPath, config: &Config) -> Result<()> {
+    let GetItemPath {
+        filename,
+        id,
+        allow_invalid_utf8_path,
+        id_only,
+        format,
+    } = action;
 
-        let items = list_deletable_fs_items(&trash_dir)?;
+    debug!("Listing trash items...");
 
-        warn!("> Deleting all {} items...", items.len());
+    let item = expect_single_trash_item(filename.as_deref(), id.as_deref(), &[], false, config)?;
 
-        let pbr = ProgressBar::new(items.len().try_into().unwrap());
+    if id_only {
+        println!("{}", item.data.compute_id());
+        return Ok(());
+    }
 
-        pbr.set_style(ProgressStyle::default_bar()
-            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {human_pos}/{human_len} ({eta})")
-            .expect("Invalid progress bar template")
-            .progress_chars("#>-"));
+    let item_path = item.complete_trash_item_path();
 
-        for (i, item) in items.iter().enumerate() {
-            let metadata = item
-                .symlink_metadata()
-                .with_context(|| format!("Failed to get metadata for item: {}", item.display()))?
-                .file_type();
-
-            if metadata.is_dir() {
-                fs::remove_dir(item)
-                    .with_context(|| format!("Failed to remove directory: {}", item.display()))?;
+    let path = match item_path.to_str() {
+        Some(path) => path.to_owned(),
+        None => {
+            if allow_invalid_utf8_path {
+                item_path.to_string_lossy().into_owned()
             } else {
-                fs::remove_file(item)
-                    .with_context(|| format!("Failed to remove file: {}", item.display()))?;
+                bail!(
+                    "Path contains invalid UTF-8 characters (lossy: {})",
+                    item_path.to_string_lossy()
+                );
             }
+        }
+    };
 
-            if i % 25 == 0 || i + 1 == items.len() {
-                pbr.set_position((i + 1).try_into().unwrap());
-            }
+    match format {
+        Some(format) => println!("{}", expand_item_template(&format, &item, &path)?),
+        None => println!("{path}"),
+    }
+
+    Ok(())
+}
+
+pub fn info(action: GetItemInfos, config: &Config) -> Result<()> {
+    let GetItemInfos { filename, id } = action;
+
+    debug!("Listing trash items...");
+
+    let item = expect_single_trash_item(filename.as_deref(), id.as_deref(), &[], false, config)?;
+    let item_path = item.complete_trash_item_path();
+
+    let mt = fs::symlink_metadata(&item_path);
+
+    println!("Filename        : {}", item.data.filename_lossy());
+    println!("ID              : {}", item.data.compute_id());
+    println!(
+        "Type            : {}",
+        describe_item_type(&item_path, &mt, item.data.metadata.original_path.as_deref())
+    );
+    println!(
+        "Size            : {}",
+        describe_item_size(&mt, config.size_format)
+    );
+    println!("Deleted on      : {}", format_date(item.data.datetime));
+    println!(
+        "Original path   : {}",
+        match &item.data.metadata.original_path {
+            Some(path) => path.display().to_string(),
+            None => "unknown".to_owned(),
         }
+    );
 
-        pbr.finish();
+    if !item.data.metadata.tags.is_empty() {
+        println!("Tags            : {}", item.data.metadata.tags.join(", "));
     }
 
-    success!("Trash was successfully emptied.");
+    println!("Trash directory : {}", item.trash_dir.display());
+    println!("Path in trash   : {}", item_path.display());
 
     Ok(())
 }
 
-pub fn trash_path(config: &Config) -> Result<()> {
-    let current_dir =
-        std::env::current_dir().context("Failed to determine path to the current directory")?;
+pub fn restore(action: RestoreItem, config: &Config) -> Result<()> {
+    let RestoreItem {
+        filename,
+        to,
+        id,
+        system,
+        dry_run,
+        all_matching,
+        merge,
+        limit,
+        older_than,
+        newer_than,
+        json,
+        force,
+    } = action;
 
-    let trash_dir = determine_trash_dir_for(&current_dir, config)?;
+    if system {
+        return restore_from_system_trash(filename.as_deref().unwrap());
+    }
 
-    println!("{}", trash_dir.display());
+    let to = to.map(|to| resolve_restore_target(&to)).transpose()?;
+
+    debug!("Listing trash items...");
+
+    if filename.is_none() && id.is_none() {
+        return restore_with_ui(
+            to,
+            dry_run,
+            merge,
+            force,
+            limit,
+            (older_than, newer_than),
+            config,
+        );
+    }
+
+    if all_matching {
+        return restore_all_matching(filename.as_deref().unwrap(), to, dry_run, force, config);
+    }
+
+    let item = expect_single_trash_item(filename.as_deref(), id.as_deref(), &[], json, config)?;
+
+    ensure_restorable(&item, force)?;
+
+    let target_dir = match to {
+        Some(to) => to,
+        None => std::env::current_dir()?,
+    };
+
+    let target_path = target_dir.join(&item.data.filename);
+
+    if merge && is_real_dir(&target_path) && is_real_dir(&item.complete_trash_item_path()) {
+        return merge_out_of_trash(&item, &target_path, dry_run, config);
+    }
+
+    if fs::symlink_metadata(&target_path).is_ok() {
+        bail!(TrasherError::TargetExists(target_path));
+    }
+
+    if !target_dir.exists() {
+        bail!("Target directory '{}' does not exist", target_dir.display());
+    }
+
+    // On case-insensitive filesystems, an existing item whose name only differs by its case
+    // would be overwritten or conflict with the restored one
+    if !is_case_sensitive_fs(&target_dir)? {
+        if let Some(existing) = find_entry_ignoring_case(&target_dir, &item.data.filename)? {
+            bail!(TrasherError::TargetExists(existing));
+        }
+    }
+
+    if dry_run {
+        println!("{}", absolute_item_path(&target_path)?.display());
+        return Ok(());
+    }
+
+    move_out_of_trash(&item, &target_path, config)
+}
+
+/// Resolve the directory provided with `unrm --to`, so symbolic links pointing to directories
+/// are followed explicitly (and the right filesystem is picked to restore the item to)
+fn resolve_restore_target(to: &Path) -> Result<PathBuf> {
+    if fs::symlink_metadata(to).is_err() {
+        return Ok(to.to_owned());
+    }
+
+    let resolved = fs::canonicalize(to)
+        .with_context(|| format!("Failed to canonicalize target directory: {}", to.display()))?;
+
+    if fs::symlink_metadata(to).is_ok_and(|mt| mt.is_symlink()) {
+        info!(
+            "Target directory is a symbolic link, restoring to: {}",
+            resolved.display()
+        );
+    } else {
+        debug!("Resolved target directory: {}", resolved.display());
+    }
+
+    Ok(resolved)
+}
+
+/// Restore all items with the provided name in the same directory,
+/// renaming them if an item with the same name already exists there
+fn restore_all_matching(
+    filename: &str,
+    to: Option<PathBuf>,
+    dry_run: bool,
+    force: bool,
+    config: &Config,
+) -> Result<()> {
+    let items = match expect_trash_item(Some(filename), None, &[], config)? {
+        FoundTrashItems::Single(item) => vec![*item],
+        FoundTrashItems::Multi(items) => items,
+    };
+
+    for item in &items {
+        ensure_restorable(item, force)?;
+    }
+
+    let target_dir = match to {
+        Some(to) => to,
+        None => std::env::current_dir()?,
+    };
+
+    if !target_dir.exists() {
+        bail!("Target directory '{}' does not exist", target_dir.display());
+    }
+
+    let case_sensitive = is_case_sensitive_fs(&target_dir)?;
+
+    // Paths picked in dry-run mode, which don't exist on the disk
+    let mut planned = vec![];
+
+    for item in &items {
+        let mut target_path = target_dir.join(&item.data.filename);
+        let mut suffix = 0;
+
+        loop {
+            let taken = planned.contains(&target_path)
+                || fs::symlink_metadata(&target_path).is_ok()
+                || (!case_sensitive
+                    && find_entry_ignoring_case(&target_dir, target_path.file_name().unwrap())?
+                        .is_some());
+
+            if !taken {
+                break;
+            }
+
+            suffix += 1;
+            target_path = target_dir.join(numbered_filename(&item.data.filename, suffix));
+        }
+
+        if dry_run {
+            println!("{}", absolute_item_path(&target_path)?.display());
+            planned.push(target_path);
+            continue;
+        }
+
+        move_out_of_trash(item, &target_path, config)?;
+
+        success!(
+            "Restored item '{}' to: {}",
+            item.data.compute_id(),
+            target_path.display()
+        );
+    }
 
     Ok(())
 }
+
+/// Add a number to a filename, before its extension (e.g. `index (1).html`)
+fn numbered_filename(filename: &OsStr, number: usize) -> OsString {
+    let path = Path::new(filename);
+
+    let mut numbered = path.file_stem().unwrap_or(filename).to_owned();
+    numbered.push(format!(" ({number})"));
+
+    if let Some(ext) = path.extension() {
+        numbered.push(".");
+        numbered.push(ext);
+    }
+
+    numbered
+}
+
+/// Ensure an item isn't a possibly incomplete copy left by an interrupted transfer, unless `force` is set
+fn ensure_restorable(item: &TrashedItem, force: bool) -> Result<()> {
+    if force || item.is_known_complete() {
+        return Ok(());
+    }
+
+    let original_path = match &item.data.metadata.original_path {
+        Some(path) => format!("the original item is still at '{}'", path.display()),
+        None => "its original location is unknown".to_owned(),
+    };
+
+    bail!(
+        "Item '{}' was left in the transfer directory by an interrupted transfer and may be an incomplete copy ({original_path})\n\nTip: use --force to restore it anyway.",
+        item.data.filename_lossy()
+    )
+}
+
+/// Move an item out of the trash to the provided location
+fn move_out_of_trash(item: &TrashedItem, target_path: &Path, config: &Config) -> Result<()> {
+    let item_path = item.complete_trash_item_path();
+    let target_parent = target_path.parent().unwrap();
+
+    if !item.is_known_complete() {
+        warn!(
+            "Item '{}' was left in the transfer directory by an interrupted transfer, it may be an incomplete copy",
+            item.data.filename_lossy()
+        );
+    }
+
+    let result = if are_on_same_fs(&item_path, target_parent)? {
+        debug!("Restoring item from trash...");
+
+        fs::rename(&item_path, target_path).context("Rename operation failed")
+    } else {
+        info!("Moving file across filesystems...");
+
+        restore_across_fs(&item_path, target_path, item, config)
+    };
+
+    result.with_context(|| {
+        format!(
+            "Failed to restore item '{}' from trash",
+            item.data.filename_lossy()
+        )
+    })?;
+
+    restore_attributes(item, target_path);
+
+    item.discard_metadata()
+}
+
+/// Apply the attributes stored when an item was trashed to its restored location
+///
+/// The attributes of the item in the trash may have been lost if it was moved across filesystems.
+fn restore_attributes(item: &TrashedItem, target_path: &Path) {
+    if let Some(attributes) = &item.data.metadata.attributes {
+        if let Err(err) = attributes.apply(target_path) {
+            warn!(
+                "Failed to restore the permissions and times of item '{}': {err}",
+                target_path.display()
+            );
+        }
+    }
+}
+
+/// Restore a directory item by merging its content into an existing directory
+///
+/// Conflicts are checked before anything is moved, so the merge either fully happens or doesn't start.
+fn merge_out_of_trash(
+    item: &TrashedItem,
+    target_path: &Path,
+    dry_run: bool,
+    config: &Config,
+) -> Result<()> {
+    let item_path = item.complete_trash_item_path();
+
+    if let Some(conflict) = find_merge_conflict(&item_path, target_path)? {
+        bail!(TrasherError::TargetExists(conflict));
+    }
+
+    if dry_run {
+        println!("{}", absolute_item_path(target_path)?.display());
+        return Ok(());
+    }
+
+    let same_fs = are_on_same_fs(&item_path, target_path)?;
+
+    merge_dir_into(&item_path, target_path, same_fs, config).with_context(|| {
+        format!(
+            "Failed to merge item '{}' from trash",
+            item.data.filename_lossy()
+        )
+    })?;
+
+    restore_attributes(item, target_path);
+
+    item.discard_metadata()
+}
+
+/// Find an entry of a directory that can't be merged into another one
+fn find_merge_conflict(source: &Path, target: &Path) -> Result<Option<PathBuf>> {
+    for entry in fs::read_dir(source)
+        .with_context(|| format!("Failed to read directory: {}", source.display()))?
+    {
+        let entry = entry.context("Failed to read directory entry")?;
+        let target_path = target.join(entry.file_name());
+
+        if fs::symlink_metadata(&target_path).is_err() {
+            continue;
+        }
+
+        if !is_real_dir(&entry.path()) || !is_real_dir(&target_path) {
+            return Ok(Some(target_path));
+        }
+
+        if let Some(conflict) = find_merge_conflict(&entry.path(), &target_path)? {
+            return Ok(Some(conflict));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Move the content of a directory into another one, then remove the (now empty) source directory
+fn merge_dir_into(source: &Path, target: &Path, same_fs: bool, config: &Config) -> Result<()> {
+    for entry in fs::read_dir(source)
+        .with_context(|| format!("Failed to read directory: {}", source.display()))?
+    {
+        let entry = entry.context("Failed to read directory entry")?;
+        let source_path = entry.path();
+        let target_path = target.join(entry.file_name());
+
+        if is_real_dir(&target_path) {
+            merge_dir_into(&source_path, &target_path, same_fs, config)?;
+            continue;
+        }
+
+        debug!(
+            "Merging '{}' into '{}'...",
+            source_path.display(),
+            target_path.display()
+        );
+
+        if same_fs {
+            fs::rename(&source_path, &target_path).context("Rename operation failed")?;
+        } else {
+            let transfer = PartialTransferGuard::track(&target_path, config);
+
+            if let Err(err) = copy_item_with_retries(&source_path, &target_path, config.retries) {
+                if !config.no_cleanup {
+                    cleanup_partial_transfer(&target_path);
+                }
+
+                return Err(err.context("Failed to copy item out of the trash"));
+            }
+
+            transfer.complete();
+
+            remove_item(&source_path).context("Failed to remove merged item from the trash")?;
+        }
+    }
+
+    fs::remove_dir(source)
+        .with_context(|| format!("Failed to remove merged directory: {}", source.display()))
+}
+
+/// Check if a path is the current directory or one of its ancestors
+///
+/// Symbolic links are not followed, as only the link itself would be removed.
+fn contains_current_dir(path: &Path) -> Result<bool> {
+    if !is_real_dir(path) {
+        return Ok(false);
+    }
+
+    let current_dir =
+        std::env::current_dir().context("Failed to determine path to the current directory")?;
+
+    Ok(absolute_item_path(path).is_ok_and(|path| current_dir.starts_with(path)))
+}
+
+/// Check if a path is a directory, without following symbolic links
+fn is_real_dir(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|mt| mt.is_dir())
+}
+
+/// Restore an item to another filesystem by copying it to a temporary location first,
+/// then renaming it into place, so the trash item is only removed on full success
+fn restore_across_fs(
+    item_path: &Path,
+    target_path: &Path,
+    item: &TrashedItem,
+    config: &Config,
+) -> Result<()> {
+    let temp_path = target_path.with_file_name(format!(
+        "{RESTORE_TRANSFER_PREFIX}{}",
+        item.data.compute_id()
+    ));
+
+    let transfer = PartialTransferGuard::track(&temp_path, config);
+
+    if let Err(err) = copy_item_with_retries(item_path, &temp_path, config.retries) {
+        if !config.no_cleanup {
+            cleanup_partial_transfer(&temp_path);
+        }
+
+        return Err(err.context("Failed to copy item out of the trash"));
+    }
+
+    transfer.complete();
+
+    fs::rename(&temp_path, target_path)
+        .context("Failed to move fully transferred item to its target location")?;
+
+    remove_item(item_path).context("Failed to remove restored item from the trash")
+}
+
+/// Remove the metadata written for an item that couldn't be moved to the trash
+fn discard_metadata_after_failure(item: &TrashedItem) {
+    if let Err(err) = item.discard_metadata() {
+        warn!("{err:?}");
+    }
+}
+
+/// Remove a partially transferred item after a failed copy
+fn cleanup_partial_transfer(path: &Path) {
+    if fs::symlink_metadata(path).is_err() {
+        return;
+    }
+
+    debug!(
+        "Removing partially transferred item '{}'...",
+        path.display()
+    );
+
+    if let Err(err) = remove_item(path) {
+        warn!(
+            "Failed to remove partially transferred item '{}': {err}",
+            path.display()
+        );
+    }
+}
+
+pub fn restore_with_ui(
+    to: Option<PathBuf>,
+    dry_run: bool,
+    merge: bool,
+    force: bool,
+    limit: Option<usize>,
+    (older_than, newer_than): (Option<Duration>, Option<Duration>),
+    config: &Config,
+) -> Result<()> {
+    let mut items = list_all_trash_items(config)?;
+
+    if items.is_empty() {
+        info!("Trash is empty");
+        return Ok(());
+    }
+
+    if let Some(older_than) = older_than {
+        let cutoff = SystemTime::now()
+            .checked_sub(older_than)
+            .context("Provided duration is too large")?;
+
+        items.retain(|item| item.data.datetime < cutoff);
+    }
+
+    if let Some(newer_than) = newer_than {
+        let cutoff = SystemTime::now()
+            .checked_sub(newer_than)
+            .context("Provided duration is too large")?;
+
+        items.retain(|item| item.data.datetime >= cutoff);
+    }
+
+    if items.is_empty() {
+        info!("No item in the trash was trashed in the provided time window.");
+        return Ok(());
+    }
+
+    // Display the most recent items when only some of them can be displayed
+    if limit.is_some() {
+        items.reverse();
+    }
+
+    let to_remove = crate::fuzzy::run_fuzzy_finder(
+        items
+            .into_iter()
+            .map(|item| FuzzyFinderItem {
+                display: format!(
+                    "[{}] {}",
+                    format_date(item.data.datetime),
+                    item.data.filename_lossy()
+                ),
+                date: item.data.datetime,
+                value: item,
+            })
+            .collect(),
+        config.ignore_case,
+        limit,
+    )?;
+
+    restore(
+        RestoreItem {
+            filename: Some(to_remove.data.filename_lossy().into_owned()),
+            to,
+            id: Some(to_remove.data.compute_id().to_owned()),
+            system: false,
+            dry_run,
+            all_matching: false,
+            merge,
+            limit: None,
+            older_than: None,
+            newer_than: None,
+            json: false,
+            force,
+        },
+        config,
+    )?;
+
+    Ok(())
+}
+
+pub fn empty(action: EmptyTrash, config: &Config) -> Result<()> {
+    let EmptyTrash {
+        show_items,
+        stats,
+        confirm_name,
+        confirm_name_threshold,
+    } = action;
+
+    let trash_dirs = list_trash_dirs(config)?;
+    let items = list_all_trash_items(config)?;
+
+    if items.is_empty() {
+        info!("Trash is empty");
+        return Ok(());
+    }
+
+    warn!("You are about to delete the entire trash directories of:\n");
+
+    for trash_dir in &trash_dirs {
+        let trash_dir_items = items
+            .iter()
+            .filter(|item| &item.trash_dir == trash_dir)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        warn!(
+            "  {} ({} items)",
+            trash_dir.display(),
+            trash_dir_items.len()
+        );
+
+        if show_items && !trash_dir_items.is_empty() {
+            println!(
+                "{}",
+                table_for_items(
+                    &trash_dir_items,
+                    &TableOptions {
+                        size_format: config.size_format,
+                        ..Default::default()
+                    }
+                )
+            );
+        }
+    }
+
+    let confirmed = if confirm_name && items.len() > confirm_name_threshold {
+        // Trash directories usually share the same name, so use the one holding the most items
+        let largest_trash_dir = trash_dirs
+            .iter()
+            .max_by_key(|trash_dir| {
+                items
+                    .iter()
+                    .filter(|item| &item.trash_dir == *trash_dir)
+                    .count()
+            })
+            .context("No trash directory found")?;
+
+        let expected = largest_trash_dir
+            .file_name()
+            .context("Trash directory has no name")?
+            .to_string_lossy();
+
+        confirm_typing(
+            &format!("\nYou are about to delete {} items", items.len()),
+            &expected,
+            config,
+        )?
+    } else {
+        confirm("\nAre you sure you want to continue", config)?
+    };
+
+    if !confirmed {
+        warn!("Cancelled.");
+        return Ok(());
+    }
+
+    info!("Emptying the trash...");
+
+    let started = Instant::now();
+    let mut freed_by_dir = vec![];
+    let mut failures = vec![];
+
+    for trash_dir in trash_dirs {
+        info!("Emptying trash directory: {}", trash_dir.display());
+
+        let trash_items_count = items
+            .iter()
+            .filter(|item| item.trash_dir == trash_dir)
+            .count();
+
+        let mut freed = 0;
+
+        warn!("> Listing files and directories to delete...");
+
+        let items = list_deletable_fs_items(&trash_dir)?;
+
+        warn!("> Deleting all {} items...", items.len());
+
+        let pbr = progress_bar(
+            items.len().try_into().unwrap(),
+            "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {human_pos}/{human_len} ({eta})",
+        );
+
+        let percent = PercentProgress::new();
+
+        for (i, item) in items.iter().enumerate() {
+            // Failures don't stop the operation, so a single stubborn item doesn't leave
+            // the rest of the trash in place
+            match remove_deletable_fs_item(item) {
+                Ok(size) => {
+                    freed += size;
+
+                    // Avoid messing with the progress bar's display
+                    pbr.suspend(|| debug!("Removed: {}", item.display()));
+                }
+
+                Err(err) => failures.push(err.context(format!(
+                    "Failed to empty {}",
+                    describe_trash_dir_location(&trash_dir)
+                ))),
+            }
+
+            if i % 25 == 0 || i + 1 == items.len() {
+                pbr.set_position((i + 1).try_into().unwrap());
+            }
+
+            percent.update((i + 1).try_into().unwrap(), items.len().try_into().unwrap());
+        }
+
+        pbr.finish();
+
+        freed_by_dir.push((trash_dir, trash_items_count, freed));
+    }
+
+    if failures.is_empty() {
+        success!("Trash was successfully emptied.");
+    }
+
+    if stats {
+        print_empty_stats(&freed_by_dir, started.elapsed(), config);
+    }
+
+    if !failures.is_empty() {
+        for err in &failures {
+            error!("{err:#}");
+        }
+
+        bail!(
+            "Failed to remove {} item(s), the other ones were removed.",
+            failures.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// Remove an item listed by [`list_deletable_fs_items`], returning the freed size
+fn remove_deletable_fs_item(item: &Path) -> Result<u64> {
+    let mt = item
+        .symlink_metadata()
+        .with_context(|| format!("Failed to get metadata for item: {}", item.display()))?;
+
+    if mt.is_dir() {
+        fs::remove_dir(item)
+            .with_context(|| format!("Failed to remove directory: {}", item.display()))?;
+    } else {
+        fs::remove_file(item)
+            .with_context(|| format!("Failed to remove file: {}", item.display()))?;
+    }
+
+    Ok(if mt.is_file() { mt.len() } else { 0 })
+}
+
+pub fn find(action: FindItems, config: &Config) -> Result<()> {
+    let FindItems {
+        query,
+        sort,
+        show_score,
+    } = action;
+
+    let mut results = list_all_trash_items(config)?
+        .into_iter()
+        .filter_map(|item| {
+            let (score, _) =
+                compute_fuzzy_find_score(&query, &item.data.filename_lossy(), config.ignore_case);
+
+            (score > 0).then_some((item, score))
+        })
+        .collect::<Vec<_>>();
+
+    if results.is_empty() {
+        info!("No matching item found in the trash.");
+        return Ok(());
+    }
+
+    match sort {
+        // Ties are broken by deletion date, most recent first
+        FindSort::Score => {
+            results.sort_by_key(|(item, score)| std::cmp::Reverse((*score, item.data.datetime)))
+        }
+
+        FindSort::Date => results.sort_by_key(|(item, _)| std::cmp::Reverse(item.data.datetime)),
+
+        FindSort::Size => results.sort_by_cached_key(|(item, _)| {
+            std::cmp::Reverse(item_size(&item.complete_trash_item_path()).unwrap_or(0))
+        }),
+    }
+
+    let (items, scores): (Vec<_>, Vec<_>) = results.into_iter().unzip();
+
+    let options = TableOptions {
+        size_format: config.size_format,
+        scores: show_score.then_some(scores),
+        ..Default::default()
+    };
+
+    println!("{}", table_for_items(&items, &options));
+
+    Ok(())
+}
+
+pub fn decode(action: DecodeTrashFilename) -> Result<()> {
+    let DecodeTrashFilename { trash_filename, tz } = action;
+
+    // Accept full paths to items as well
+    let trash_filename = Path::new(&trash_filename)
+        .file_name()
+        .unwrap_or(&trash_filename);
+
+    let data = TrashItemInfos::decode(trash_filename).with_context(|| {
+        format!(
+            "Failed to decode trash filename '{}'",
+            trash_filename.to_string_lossy()
+        )
+    })?;
+
+    let deleted_on = match tz {
+        Some(tz) => format_date_in(data.datetime, tz),
+        None => format_date(data.datetime),
+    };
+
+    println!("Filename   : {}", data.filename_lossy());
+    println!("ID         : {}", data.compute_id());
+    println!("Deleted on : {deleted_on}");
+
+    Ok(())
+}
+
+pub fn size(action: ComputeTrashSize, config: &Config) -> Result<()> {
+    let ComputeTrashSize { total_only, human } = action;
+
+    let mut sizes_by_dir = vec![];
+
+    for trash_dir in list_trash_dirs(config)? {
+        let items = list_trash_items(&trash_dir, config)?;
+        let mut size = 0;
+
+        for item in &items {
+            let path = item.complete_trash_item_path();
+
+            match item_size(&path) {
+                Ok(item_size) => size += item_size,
+                Err(err) => warn!(
+                    "Failed to compute size of item '{}': {err:?}",
+                    path.display()
+                ),
+            }
+        }
+
+        sizes_by_dir.push((trash_dir, items.len(), size));
+    }
+
+    let total_items = sizes_by_dir
+        .iter()
+        .map(|(_, items, _)| items)
+        .sum::<usize>();
+    let total_size = sizes_by_dir.iter().map(|(_, _, size)| size).sum::<u64>();
+
+    if total_only {
+        if human {
+            println!("{}", human_readable_size(total_size, config.size_format));
+        } else {
+            println!("{total_size}");
+        }
+
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+
+    table
+        .load_preset(UTF8_FULL_CONDENSED)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(vec!["Trash directory", "Items", "Size"]);
+
+    for (trash_dir, items_count, size) in &sizes_by_dir {
+        table.add_row(vec![
+            trash_dir.to_string_lossy().into_owned(),
+            items_count.to_string(),
+            human_readable_size(*size, config.size_format),
+        ]);
+    }
+
+    table.add_row(vec![
+        "Total".to_owned(),
+        total_items.to_string(),
+        human_readable_size(total_size, config.size_format),
+    ]);
+
+    println!("{table}");
+
+    Ok(())
+}
+
+/// Display a summary of the items removed by the [`empty`] command
+fn print_empty_stats(freed_by_dir: &[(PathBuf, usize, u64)], elapsed: Duration, config: &Config) {
+    let mut table = Table::new();
+
+    table
+        .load_preset(UTF8_FULL_CONDENSED)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(vec!["Trash directory", "Items", "Freed"]);
+
+    for (trash_dir, items_count, freed) in freed_by_dir {
+        table.add_row(vec![
+            trash_dir.to_string_lossy().into_owned(),
+            items_count.to_string(),
+            human_readable_size(*freed, config.size_format),
+        ]);
+    }
+
+    table.add_row(vec![
+        "Total".to_owned(),
+        freed_by_dir
+            .iter()
+            .map(|(_, items_count, _)| items_count)
+            .sum::<usize>()
+            .to_string(),
+        human_readable_size(
+            freed_by_dir.iter().map(|(_, _, freed)| freed).sum(),
+            config.size_format,
+        ),
+    ]);
+
+    println!("{table}");
+
+    info!("Completed in {:.2}s", elapsed.as_secs_f64());
+}
+
+pub fn purge(action: PurgeItems, config: &Config) -> Result<()> {
+    let PurgeItems {
+        older_than,
+        apply_policy,
+        policy_file,
+        watch,
+        interval,
+    } = action;
+
+    let rules = if apply_policy {
+        let policy_file = match policy_file {
+            Some(path) => path,
+            None => default_retention_policy_path()?,
+        };
+
+        Some(load_retention_policy(&policy_file)?)
+    } else {
+        None
+    };
+
+    let purge_once = |interrupted: Option<&AtomicBool>| match (&rules, older_than) {
+        (Some(rules), _) => apply_retention_policy(rules, config, interrupted),
+        (None, Some(older_than)) => purge_items_older_than(older_than, config, interrupted),
+        (None, None) => unreachable!(),
+    };
+
+    if !watch {
+        let purged = purge_once(None)?;
+        success!("Purged {purged} item(s) from the trash.");
+        return Ok(());
+    }
+
+    let interrupted = Arc::new(AtomicBool::new(false));
+
+    register_stop_flag(&interrupted).context("Failed to register the interruption handler")?;
+
+    info!("Watching the trash, press Ctrl+C to stop.");
+
+    loop {
+        match purge_once(Some(&interrupted)) {
+            Ok(purged) => info!(
+                "[{}] Purged {purged} item(s).",
+                Zoned::now().strftime("%F %T")
+            ),
+            Err(err) => error!("[{}] Purge failed: {err:?}", Zoned::now().strftime("%F %T")),
+        }
+
+        let next_purge = Instant::now() + interval;
+
+        while !interrupted.load(Ordering::SeqCst) && Instant::now() < next_purge {
+            thread::sleep(
+                next_purge
+                    .saturating_duration_since(Instant::now())
+                    .min(Duration::from_millis(200)),
+            );
+        }
+
+        if interrupted.load(Ordering::SeqCst) {
+            info!("Interrupted, stopping.");
+            return Ok(());
+        }
+    }
+}
+
+/// Permanently delete all trash items older than the provided duration,
+/// stopping early if the provided interruption flag is raised
+fn purge_items_older_than(
+    older_than: Duration,
+    config: &Config,
+    interrupted: Option<&AtomicBool>,
+) -> Result<usize> {
+    let cutoff = SystemTime::now()
+        .checked_sub(older_than)
+        .context("Provided duration is too large")?;
+
+    let items = list_all_trash_items(config)?
+        .into_iter()
+        .filter(|item| item.data.datetime < cutoff)
+        .collect::<Vec<_>>();
+
+    debug!("Found {} item(s) to purge.", items.len());
+
+    let mut purged = 0;
+
+    for item in items {
+        if interrupted.is_some_and(|interrupted| interrupted.load(Ordering::SeqCst)) {
+            break;
+        }
+
+        purge_item(&item)?;
+        purged += 1;
+    }
+
+    Ok(purged)
+}
+
+/// Apply retention rules to every trash directory, stopping early if the provided interruption flag is raised
+fn apply_retention_policy(
+    rules: &[RetentionRule],
+    config: &Config,
+    interrupted: Option<&AtomicBool>,
+) -> Result<usize> {
+    let mut purged = 0;
+
+    for trash_dir in list_trash_dirs(config)? {
+        let Some(rule) = find_retention_rule(rules, &trash_dir) else {
+            debug!(
+                "No retention rule applies to trash directory: {}",
+                trash_dir.display()
+            );
+
+            continue;
+        };
+
+        debug!(
+            "Applying retention rule for '{}' to trash directory: {}",
+            rule.prefix.display(),
+            trash_dir.display()
+        );
+
+        let mut items = list_trash_items(&trash_dir, config)?;
+        items.sort_by_key(|item| item.data.datetime);
+
+        if let Some(max_age) = rule.max_age {
+            let cutoff = SystemTime::now()
+                .checked_sub(max_age)
+                .context("Maximum age is too large")?;
+
+            let expired = items.iter().take_while(|item| item.data.datetime < cutoff);
+
+            for item in expired {
+                if interrupted.is_some_and(|interrupted| interrupted.load(Ordering::SeqCst)) {
+                    return Ok(purged);
+                }
+
+                purge_item(item)?;
+                purged += 1;
+            }
+
+            items.retain(|item| item.data.datetime >= cutoff);
+        }
+
+        if let Some(max_size) = rule.max_size {
+            // Items whose size can't be computed are left out, instead of aborting the whole policy
+            let sized_items = items
+                .iter()
+                .filter_map(|item| {
+                    let path = item.complete_trash_item_path();
+
+                    match item_size(&path) {
+                        Ok(size) => Some((item, size)),
+                        Err(err) => {
+                            warn!(
+                                "Failed to compute size of item '{}': {err:?}",
+                                path.display()
+                            );
+
+                            None
+                        }
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            let mut total_size = sized_items.iter().map(|(_, size)| size).sum::<u64>();
+
+            // Remove the oldest items first until the trash fits in the allowed size
+            for (item, size) in sized_items {
+                if total_size <= max_size {
+                    break;
+                }
+
+                if interrupted.is_some_and(|interrupted| interrupted.load(Ordering::SeqCst)) {
+                    return Ok(purged);
+                }
+
+                purge_item(item)?;
+                purged += 1;
+                total_size -= size;
+            }
+        }
+    }
+
+    Ok(purged)
+}
+
+/// Permanently delete an item from the trash
+fn purge_item(item: &TrashedItem) -> Result<()> {
+    debug!(
+        "Purging item '{}' from trash ({})...",
+        item.data.filename_lossy(),
+        item.complete_trash_item_path().display()
+    );
+
+    remove_item(&item.complete_trash_item_path()).with_context(|| {
+        format!(
+            "Failed to remove item '{}' from {}",
+            item.data.filename_lossy(),
+            describe_trash_dir_location(&item.trash_dir)
+        )
+    })?;
+
+    item.discard_metadata()
+}
+
+pub fn doctor(action: CheckTrash, config: &Config) -> Result<()> {
+    let CheckTrash { fix } = action;
+
+    let mut issues = 0;
+
+    for trash_dir in list_trash_dirs(config)? {
+        info!("Checking trash directory: {}", trash_dir.display());
+
+        for issue in find_trash_issues(&trash_dir, config)? {
+            issues += 1;
+
+            warn!("> {issue}");
+
+            if fix {
+                let description = fix_trash_issue(&trash_dir, &issue)?;
+                success!("  Fixed: {description}");
+            }
+        }
+    }
+
+    if issues == 0 {
+        success!("No inconsistency found.");
+    } else if !fix {
+        warn!("\nFound {issues} issue(s), use --fix to repair them.");
+    } else {
+        success!("\nRepaired {issues} issue(s).");
+    }
+
+    Ok(())
+}
+
+pub fn compact(action: CompactTrash, config: &Config) -> Result<()> {
+    let CompactTrash { dry_run } = action;
+
+    let mut removed = 0;
+
+    for trash_dir in list_trash_dirs(config)? {
+        if !is_empty_trash_dir(&trash_dir, config)? {
+            debug!("Trash directory is not empty: {}", trash_dir.display());
+            continue;
+        }
+
+        if dry_run {
+            println!("{}", trash_dir.display());
+            continue;
+        }
+
+        info!("Removing empty trash directory: {}", trash_dir.display());
+
+        for entry in fs::read_dir(&trash_dir).context("Failed to read trash directory")? {
+            let entry = entry.context("Failed to read trash directory entry")?;
+
+            if entry.file_name() == TRASH_RECOVERY_FILENAME {
+                fs::remove_file(entry.path()).with_context(|| {
+                    format!("Failed to remove file: {}", entry.path().display())
+                })?;
+
+                continue;
+            }
+
+            fs::remove_dir(entry.path()).with_context(|| {
+                format!("Failed to remove directory: {}", entry.path().display())
+            })?;
+        }
+
+        fs::remove_dir(&trash_dir).with_context(|| {
+            format!("Failed to remove trash directory: {}", trash_dir.display())
+        })?;
+
+        removed += 1;
+    }
+
+    if !dry_run {
+        success!("Removed {removed} empty trash directory(ies).");
+    }
+
+    Ok(())
+}
+
+pub fn trash_path(action: GetTrashPath, config: &Config) -> Result<()> {
+    let GetTrashPath {
+        path,
+        all,
+        include_potential,
+    } = action;
+
+    if all {
+        let trash_dirs = if include_potential {
+            list_potential_trash_dirs(config)?
+        } else {
+            list_trash_dirs(config)?
+        };
+
+        for trash_dir in trash_dirs {
+            println!("{}", trash_dir.display());
+        }
+
+        return Ok(());
+    }
+
+    let trash_dir = match path {
+        Some(path) => determine_trash_dir_for_lenient(&path, config)?,
+        None => {
+            let current_dir = std::env::current_dir()
+                .context("Failed to determine path to the current directory")?;
+
+            determine_trash_dir_for(&current_dir, config)?
+        }
+    };
+
+    println!("{}", trash_dir.display());
+
+    Ok(())
+}
+
+pub fn commit(action: CommitPending, config: &Config) -> Result<()> {
+    let CommitPending { all } = action;
+
+    let pending = load_pending_deletions()?;
+
+    if pending.is_empty() {
+        info!("No pending deletion.");
+        return Ok(());
+    }
+
+    let mut remaining = vec![];
+    let mut committed = 0;
+
+    for deletion in pending {
+        if !all && !deletion.is_expired() {
+            debug!(
+                "Delay for item '{}' has not expired yet",
+                deletion.original_path.display()
+            );
+
+            remaining.push(deletion);
+            continue;
+        }
+
+        if let Err(err) = commit_deletion(&deletion, config) {
+            error!(
+                "Failed to move item '{}' to the trash: {err:?}",
+                deletion.original_path.display()
+            );
+
+            remaining.push(deletion);
+            continue;
+        }
+
+        committed += 1;
+    }
+
+    save_pending_deletions(&remaining)?;
+
+    success!(
+        "{committed} item(s) moved to the trash, {} deletion(s) still pending.",
+        remaining.len()
+    );
+
+    Ok(())
+}
+
+/// Move an item whose deletion was pending to the trash, under its original name
+fn commit_deletion(deletion: &PendingDeletion, config: &Config) -> Result<()> {
+    let PendingDeletion {
+        original_path,
+        pending_path,
+        deadline: _,
+        tags,
+    } = deletion;
+
+    if fs::symlink_metadata(original_path).is_ok() {
+        bail!(TrasherError::TargetExists(original_path.clone()));
+    }
+
+    // Put the item back in place so it's trashed exactly like with a direct 'rm'
+    fs::rename(pending_path, original_path).with_context(|| {
+        format!(
+            "Failed to move hidden item '{}' back in place",
+            pending_path.display()
+        )
+    })?;
+
+    let result = remove(
+        MoveToTrash {
+            paths: vec![original_path.clone()],
+            allow_invalid_utf8_item_names: true,
+            tag: tags.clone(),
+            // A summary is displayed once all deletions are committed
+            quiet: true,
+            ..Default::default()
+        },
+        config,
+    );
+
+    // Hide the item again so the deletion stays pending, as it's kept in the store
+    if result.is_err() && fs::symlink_metadata(original_path).is_ok() {
+        fs::rename(original_path, pending_path).with_context(|| {
+            format!(
+                "Failed to hide item '{}' again after it couldn't be moved to the trash",
+                original_path.display()
+            )
+        })?;
+    }
+
+    result
+}
+
+pub fn abort(action: AbortPending) -> Result<()> {
+    let AbortPending { name } = action;
+
+    let pending = load_pending_deletions()?;
+
+    let (aborted, mut remaining): (Vec<_>, Vec<_>) =
+        pending.into_iter().partition(|deletion| match &name {
+            Some(name) => deletion
+                .original_path
+                .file_name()
+                .is_some_and(|filename| filename == name.as_str()),
+            None => true,
+        });
+
+    if aborted.is_empty() {
+        info!("No pending deletion to cancel.");
+        return Ok(());
+    }
+
+    let mut restored = 0;
+
+    for deletion in aborted {
+        if fs::symlink_metadata(&deletion.original_path).is_ok() {
+            error!(
+                "Cannot put item back in place as its original path is now occupied: {}",
+                deletion.original_path.display()
+            );
+
+            remaining.push(deletion);
+            continue;
+        }
+
+        if let Err(err) = fs::rename(&deletion.pending_path, &deletion.original_path) {
+            error!(
+                "Failed to put item '{}' back in place: {err}",
+                deletion.original_path.display()
+            );
+
+            remaining.push(deletion);
+            continue;
+        }
+
+        restored += 1;
+    }
+
+    save_pending_deletions(&remaining)?;
+
+    success!("{restored} item(s) put back in place.");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Mutex, MutexGuard, PoisonError};
+
+    use clap::Parser;
+
+    use super::*;
+
+    /// Lock held by tests depending on the current directory, which is shared by the whole process
+    static CURRENT_DIR_LOCK: Mutex<()> = Mutex::new(());
+
+    /// Current directory change, reverted when dropped
+    struct CurrentDirChange {
+        previous: PathBuf,
+        _lock: MutexGuard<'static, ()>,
+    }
+
+    impl CurrentDirChange {
+        fn lock() -> MutexGuard<'static, ()> {
+            CURRENT_DIR_LOCK
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+        }
+
+        fn to(dir: &Path) -> Self {
+            let lock = Self::lock();
+            let previous = std::env::current_dir().unwrap();
+
+            std::env::set_current_dir(dir).unwrap();
+
+            Self {
+                previous,
+                _lock: lock,
+            }
+        }
+    }
+
+    impl Drop for CurrentDirChange {
+        fn drop(&mut self) {
+            std::env::set_current_dir(&self.previous).unwrap();
+        }
+    }
+
+    #[test]
+    fn trash_to_custom_trash_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let custom_trash_dir = dir.path().join("custom");
+
+        let config = Config::for_tests(&dir.path().join("trash"), &[]);
+
+        let path = dir.path().join("report.txt");
+        fs::write(&path, "content").unwrap();
+
+        let rm_args = |create: bool| {
+            let mut args = vec![
+                "rm".as_ref(),
+                "--to-trash-dir".as_ref(),
+                custom_trash_dir.as_os_str(),
+                path.as_os_str(),
+            ];
+
+            if create {
+                args.push("--create-trash-dir".as_ref());
+            }
+
+            MoveToTrash::parse_from(args)
+        };
+
+        let err = remove(rm_args(false), &config).unwrap_err();
+        assert!(err.to_string().contains("--create-trash-dir"));
+        assert!(path.exists());
+        assert!(!custom_trash_dir.exists());
+
+        remove(rm_args(true), &config).unwrap();
+        assert!(!path.exists());
+        assert!(custom_trash_dir.join(TRASH_RECOVERY_FILENAME).exists());
+
+        let items = list_trash_items(&custom_trash_dir, &config).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].data.filename, "report.txt");
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn merged_dir_gets_its_attributes_back() {
+        use std::{
+            fs::{File, FileTimes},
+            os::unix::fs::PermissionsExt,
+            time::UNIX_EPOCH,
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::for_tests(&dir.path().join("trash"), &[]);
+
+        let project = dir.path().join("project");
+        let modified = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        fs::create_dir(&project).unwrap();
+        fs::write(project.join("report.txt"), "content").unwrap();
+        File::open(&project)
+            .unwrap()
+            .set_times(FileTimes::new().set_modified(modified))
+            .unwrap();
+        fs::set_permissions(&project, fs::Permissions::from_mode(0o750)).unwrap();
+
+        remove(
+            MoveToTrash::parse_from(["rm".as_ref(), project.as_os_str()]),
+            &config,
+        )
+        .unwrap();
+
+        fs::create_dir(&project).unwrap();
+        fs::write(project.join("notes.txt"), "notes").unwrap();
+
+        restore(
+            RestoreItem::parse_from([
+                "unrm".as_ref(),
+                "project".as_ref(),
+                "--merge".as_ref(),
+                "--to".as_ref(),
+                dir.path().as_os_str(),
+            ]),
+            &config,
+        )
+        .unwrap();
+
+        assert!(project.join("report.txt").exists());
+        assert!(project.join("notes.txt").exists());
+
+        let mt = fs::metadata(&project).unwrap();
+        assert_eq!(mt.permissions().mode() & 0o777, 0o750);
+        assert_eq!(mt.modified().unwrap(), modified);
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn describe_and_drop_relative_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::for_tests(&dir.path().join("trash"), &[]);
+
+        fs::create_dir(dir.path().join("data")).unwrap();
+        fs::write(dir.path().join("data/report.txt"), "content").unwrap();
+
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink("data", &link).unwrap();
+
+        remove(
+            MoveToTrash::parse_from(["rm".as_ref(), link.as_os_str()]),
+            &config,
+        )
+        .unwrap();
+
+        // The link's target is resolved from its original location, not from the trash directory
+        let items = list_all_trash_items(&config).unwrap();
+        let item_path = items[0].complete_trash_item_path();
+        assert_eq!(
+            describe_item_type(
+                &item_path,
+                &fs::symlink_metadata(&item_path),
+                items[0].data.metadata.original_path.as_deref()
+            ),
+            "Symlink -> data"
+        );
+        assert_eq!(
+            describe_item_type(&item_path, &fs::symlink_metadata(&item_path), None),
+            "Symlink -> data"
+        );
+
+        // Dropping the link must not remove its target's content
+        drop(DropItem::parse_from(["drop", "link"]), &config).unwrap();
+        assert!(fs::symlink_metadata(&item_path).is_err());
+        assert!(dir.path().join("data/report.txt").exists());
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn trash_and_restore_broken_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::for_tests(&dir.path().join("trash"), &[]);
+
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(dir.path().join("nonexistent"), &link).unwrap();
+
+        remove(
+            MoveToTrash::parse_from(["rm".as_ref(), link.as_os_str()]),
+            &config,
+        )
+        .unwrap();
+        assert!(fs::symlink_metadata(&link).is_err());
+
+        let items = list_all_trash_items(&config).unwrap();
+        assert_eq!(items.len(), 1);
+
+        let item_path = items[0].complete_trash_item_path();
+        assert_eq!(
+            describe_item_type(
+                &item_path,
+                &fs::symlink_metadata(&item_path),
+                items[0].data.metadata.original_path.as_deref()
+            ),
+            format!(
+                "Broken symlink -> {}",
+                dir.path().join("nonexistent").display()
+            )
+        );
+        assert_eq!(ItemDescription::new(&items[0]).item_type, "symlink");
+
+        restore(
+            RestoreItem::parse_from([
+                "unrm".as_ref(),
+                "link".as_ref(),
+                "--to".as_ref(),
+                dir.path().as_os_str(),
+            ]),
+            &config,
+        )
+        .unwrap();
+
+        assert_eq!(
+            fs::read_link(&link).unwrap(),
+            dir.path().join("nonexistent")
+        );
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn restore_to_symlinked_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::for_tests(&dir.path().join("trash"), &[]);
+
+        let real_dir = dir.path().join("real");
+        let link = dir.path().join("link");
+        fs::create_dir(&real_dir).unwrap();
+        std::os::unix::fs::symlink(&real_dir, &link).unwrap();
+
+        assert_eq!(
+            resolve_restore_target(&link).unwrap(),
+            fs::canonicalize(&real_dir).unwrap()
+        );
+
+        let path = dir.path().join("report.txt");
+        fs::write(&path, "content").unwrap();
+
+        remove(
+            MoveToTrash::parse_from(["rm".as_ref(), path.as_os_str()]),
+            &config,
+        )
+        .unwrap();
+
+        restore(
+            RestoreItem::parse_from([
+                "unrm".as_ref(),
+                "report.txt".as_ref(),
+                "--to".as_ref(),
+                link.as_os_str(),
+            ]),
+            &config,
+        )
+        .unwrap();
+
+        assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+        assert_eq!(
+            fs::read_to_string(real_dir.join("report.txt")).unwrap(),
+            "content"
+        );
+    }
+
+    #[test]
+    fn refuse_to_trash_dir_containing_trash_dir() {
+        let _lock = CurrentDirChange::lock();
+
+        let dir = tempfile::tempdir().unwrap();
+        let project = fs::canonicalize(dir.path()).unwrap().join("project");
+        let config = Config::for_tests(&project.join("trash"), &[]);
+
+        fs::create_dir(&project).unwrap();
+        fs::write(project.join("file"), "").unwrap();
+
+        for path in [&project, &project.join("trash")] {
+            fs::create_dir_all(project.join("trash")).unwrap();
+
+            let err = remove(
+                MoveToTrash::parse_from(["rm".as_ref(), path.as_os_str()]),
+                &config,
+            )
+            .unwrap_err();
+
+            assert!(err
+                .to_string()
+                .contains("is or contains the trash directory"));
+            assert!(project.join("file").exists());
+            assert!(project.join("trash").is_dir());
+        }
+    }
+
+    #[test]
+    fn trash_current_and_parent_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = fs::canonicalize(dir.path()).unwrap();
+        let config = Config::for_tests(&dir.join("trash"), &["--assume-yes"]);
+
+        fs::create_dir_all(dir.join("current")).unwrap();
+        fs::create_dir_all(dir.join("parent").join("child")).unwrap();
+
+        {
+            let _cd = CurrentDirChange::to(&dir.join("current"));
+            remove(MoveToTrash::parse_from(["rm", "."]), &config).unwrap();
+        }
+
+        {
+            let _cd = CurrentDirChange::to(&dir.join("parent").join("child"));
+            remove(MoveToTrash::parse_from(["rm", ".."]), &config).unwrap();
+        }
+
+        assert!(!dir.join("current").exists());
+        assert!(!dir.join("parent").exists());
+
+        let mut filenames = list_all_trash_items(&config)
+            .unwrap()
+            .into_iter()
+            .map(|item| item.data.filename)
+            .collect::<Vec<_>>();
+
+        filenames.sort();
+        assert_eq!(filenames, ["current", "parent"]);
+    }
+
+    #[test]
+    fn trash_and_restore_item_named_like_trash_item() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::for_tests(&dir.path().join("trash"), &[]);
+
+        let path = dir.path().join("report ^AbC");
+        fs::write(&path, "content").unwrap();
+
+        remove(
+            MoveToTrash::parse_from(["rm".as_ref(), path.as_os_str()]),
+            &config,
+        )
+        .unwrap();
+        assert!(fs::symlink_metadata(&path).is_err());
+
+        let items = list_all_trash_items(&config).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].data.filename, "report ^AbC");
+
+        restore(
+            RestoreItem::parse_from([
+                "unrm".as_ref(),
+                "report ^AbC".as_ref(),
+                "--to".as_ref(),
+                dir.path().as_os_str(),
+            ]),
+            &config,
+        )
+        .unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "content");
+        assert!(list_all_trash_items(&config).unwrap().is_empty());
+    }
+
+    #[test]
+    fn stranded_transfer_requires_force_to_be_restored() {
+        let dir = tempfile::tempdir().unwrap();
+        let trash_dir = dir.path().join("trash");
+        let config = Config::for_tests(&trash_dir, &[]);
+
+        // Simulate a transfer interrupted before the original item was removed
+        let original_path = dir.path().join("report.txt");
+        fs::write(&original_path, "content").unwrap();
+
+        let mut data = TrashItemInfos::new_now(OsString::from("report.txt"));
+        data.metadata.original_path = Some(original_path.clone());
+
+        let item = TrashedItem {
+            data,
+            trash_dir: trash_dir.clone(),
+            pending_in: None,
+        };
+
+        let transfer_path = item.transfer_trash_item_path(&config);
+        fs::create_dir_all(transfer_path.parent().unwrap()).unwrap();
+        fs::write(&transfer_path, "partial").unwrap();
+        item.write_metadata(false).unwrap();
+
+        let items = list_all_trash_items(&config).unwrap();
+        assert_eq!(items.len(), 1);
+        assert!(items[0].is_pending());
+        assert!(!items[0].is_known_complete());
+        assert!(ItemDescription::new(&items[0]).incomplete);
+
+        let target_dir = dir.path().join("restored");
+        fs::create_dir(&target_dir).unwrap();
+
+        let restore_args = |force: bool| {
+            let mut args = vec![
+                "unrm".as_ref(),
+                "report.txt".as_ref(),
+                "--to".as_ref(),
+                target_dir.as_os_str(),
+            ];
+
+            if force {
+                args.push("--force".as_ref());
+            }
+
+            RestoreItem::parse_from(args)
+        };
+
+        assert!(restore(restore_args(false), &config).is_err());
+        assert!(transfer_path.exists());
+
+        restore(restore_args(true), &config).unwrap();
+        assert_eq!(
+            fs::read_to_string(target_dir.join("report.txt")).unwrap(),
+            "partial"
+        );
+    }
+
+    #[test]
+    fn refuse_relative_custom_trash_dir_inside_item() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::for_tests(&dir.path().join("trash"), &[]);
+
+        fs::create_dir(dir.path().join("project")).unwrap();
+
+        let _cwd = CurrentDirChange::to(dir.path());
+
+        for keep_copy in [false, true] {
+            let mut args = vec![
+                "rm",
+                "--to-trash-dir",
+                "./project/trash",
+                "--create-trash-dir",
+            ];
+
+            if keep_copy {
+                args.push("--keep-copy");
+            }
+
+            args.push("project");
+
+            let err = remove(MoveToTrash::parse_from(args), &config).unwrap_err();
+            assert!(err.to_string().contains("contains the trash directory"));
+            assert!(dir.path().join("project").is_dir());
+        }
+
+        // Items moved to a relative trash directory are recorded under its absolute path
+        fs::write(dir.path().join("report.txt"), "content").unwrap();
+
+        remove(
+            MoveToTrash::parse_from(["rm", "--to-trash-dir", "./project/trash", "report.txt"]),
+            &config,
+        )
+        .unwrap();
+
+        let trash_dir = fs::canonicalize(dir.path().join("project/trash")).unwrap();
+        let items = list_trash_items(&trash_dir, &config).unwrap();
+        assert_eq!(items.len(), 1);
+        assert!(items[0].trash_dir.is_absolute());
+    }
+
+    #[test]
+    fn trash_summaries() {
+        assert_eq!(
+            trash_summary(2, Some(1536), 1, SizeFormat::Iec),
+            "Trashed 2 item(s) (1.50 KiB total) to 1 trash director(ies)."
+        );
+
+        // The size of directories renamed into the trash isn't computed
+        assert_eq!(
+            trash_summary(3, None, 2, SizeFormat::Iec),
+            "Trashed 3 item(s) to 2 trash director(ies)."
+        );
+    }
+
+    #[test]
+    fn failed_pending_deletion_is_hidden_again() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // A trash directory can't be created in place of a regular file
+        let trash_root = dir.path().join("trash");
+        fs::write(&trash_root, "").unwrap();
+
+        let original_path = dir.path().join("report.txt");
+        let pending_path = dir.path().join(format!("{PENDING_ITEM_PREFIX}report.txt"));
+        fs::write(&pending_path, "content").unwrap();
+
+        let deletion = PendingDeletion::new(
+            original_path.clone(),
+            pending_path.clone(),
+            Duration::ZERO,
+            vec![],
+        );
+
+        assert!(commit_deletion(&deletion, &Config::for_tests(&trash_root, &[])).is_err());
+
+        assert!(fs::symlink_metadata(&original_path).is_err());
+        assert_eq!(fs::read_to_string(&pending_path).unwrap(), "content");
+    }
+}
@@ -1,13 +1,44 @@
-use std::path::PathBuf;
+use std::{
+    ffi::OsString,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
 
 use clap::{Args, Parser, Subcommand};
+use globset::Glob;
+use jiff::tz::TimeZone;
+
+use crate::{
+    dates::{parse_date, parse_duration, parse_time_zone},
+    fsutils::{
+        parse_item_name, parse_item_template, parse_size, parse_transfer_dir_name, SizeFormat,
+    },
+};
+
+/// Exit codes, as listed in the help message
+const EXIT_CODES_HELP: &str = "\
+Exit codes:
+  0  Success
+  1  Generic failure
+  2  Invalid command-line arguments (usage error)
+  3  The requested item was not found in the trash
+  4  Multiple items match the provided name and no ID was provided
+  5  A filesystem operation was denied (permission denied)
+  6  The target of the operation already exists";
 
 #[derive(Parser)]
-#[clap(author, version, about, long_about = None)]
+#[clap(author, version, about, long_about = None, after_help = EXIT_CODES_HELP)]
 pub struct Opts {
     #[clap(global = true, short, long)]
     pub verbose: bool,
 
+    #[clap(
+        global = true,
+        long,
+        help = "Don't display progress bars (messages are still displayed)"
+    )]
+    pub no_progress: bool,
+
     #[clap(subcommand)]
     pub action: Action,
 
@@ -23,6 +54,107 @@ pub struct Config {
         help = "Disallow making a filesystem-local trash directory in some paths"
     )]
     pub exclude: Vec<PathBuf>,
+
+    #[clap(
+        global = true,
+        long,
+        help = "Read directories to exclude (like with --exclude) from a file, one per line"
+    )]
+    pub exclude_from: Vec<PathBuf>,
+
+    #[clap(
+        global = true,
+        long,
+        help = "Add a directory to the list of directories to never create a trash directory for"
+    )]
+    pub always_exclude: Vec<PathBuf>,
+
+    #[clap(
+        global = true,
+        long,
+        value_parser = parse_glob,
+        help = "Disallow making a filesystem-local trash directory in paths matching a glob pattern (e.g. '**/node_modules')"
+    )]
+    pub exclude_pattern: Vec<Glob>,
+
+    #[clap(
+        global = true,
+        short = 'I',
+        long,
+        help = "Ignore case when filtering items by name (by default, case is only taken into account when the query contains an uppercase letter)"
+    )]
+    pub ignore_case: bool,
+
+    #[clap(
+        global = true,
+        long,
+        help = "Keep partially transferred items when a transfer across filesystems fails instead of removing them"
+    )]
+    pub no_cleanup: bool,
+
+    #[clap(
+        global = true,
+        long,
+        default_value_t = 0,
+        help = "Number of times to retry transfers across filesystems failing with a transient error (e.g. on network mounts)"
+    )]
+    pub retries: u32,
+
+    #[clap(
+        global = true,
+        short = 'y',
+        long,
+        help = "Answer yes to all confirmation prompts"
+    )]
+    pub assume_yes: bool,
+
+    /// Fail instead of asking for a confirmation (e.g. when serving requests, as stdin isn't the client's)
+    #[clap(skip)]
+    pub non_interactive: bool,
+
+    #[clap(
+        global = true,
+        long,
+        value_enum,
+        default_value_t = SizeFormat::Iec,
+        help = "Units to display sizes with: 1024-based (iec, e.g. KiB) or 1000-based (si, e.g. kB)"
+    )]
+    pub size_format: SizeFormat,
+
+    #[clap(
+        global = true,
+        long,
+        env = "TRASHER_TRANSFER_DIR_NAME",
+        value_parser = parse_transfer_dir_name,
+        help = "Name of the directory partial transfers are staged in, inside trash directories (defaults to '.#PARTIAL')"
+    )]
+    pub transfer_dir_name: Option<String>,
+
+    #[clap(
+        global = true,
+        long,
+        env = "TRASHER_XDG_HOME_TRASH",
+        value_parser = clap::builder::BoolishValueParser::new(),
+        help = "Use '$XDG_DATA_HOME/trasher/trash' (defaults to '~/.local/share/trasher/trash') instead of '~/.trasher' as the home directory's trash"
+    )]
+    pub xdg_home_trash: bool,
+
+    #[clap(
+        global = true,
+        long,
+        env = "TRASHER_TRASH_ROOT",
+        help = "Use this directory as the only trash directory, instead of the ones of each mountpoint (e.g. to inspect a trash copied from elsewhere)"
+    )]
+    pub trash_root: Option<PathBuf>,
+
+    #[clap(
+        global = true,
+        long,
+        env = "TRASHER_SINGLE_TRASH",
+        value_parser = clap::builder::BoolishValueParser::new(),
+        help = "Move all items to the home directory's trash, even those located on other filesystems, and only use this trash"
+    )]
+    pub single_trash: bool,
 }
 
 #[derive(Subcommand)]
@@ -40,6 +172,18 @@ pub enum Action {
     )]
     Restore(RestoreItem),
 
+    #[clap(
+        name = "export",
+        about = "Write an item from the trash into an archive, without restoring it"
+    )]
+    Export(ExportItem),
+
+    #[clap(
+        name = "find",
+        about = "Search items in the trash with the fuzzy finder's scoring, without the interactive picker"
+    )]
+    Find(FindItems),
+
     #[clap(name = "drop", about = "Permanently delete an item from the trash")]
     Drop(DropItem),
 
@@ -49,26 +193,247 @@ pub enum Action {
     )]
     PathOf(GetItemPath),
 
+    #[clap(
+        name = "info",
+        about = "Display informations about an item in the trash"
+    )]
+    Info(GetItemInfos),
+
     #[clap(
         name = "trash-path",
         about = "Get the path of the trash directory for the current folder"
     )]
-    TrashPath,
+    TrashPath(GetTrashPath),
+
+    #[clap(
+        name = "decode",
+        about = "Decode the name of an item inside a trash directory"
+    )]
+    Decode(DecodeTrashFilename),
+
+    #[clap(
+        name = "size",
+        visible_alias = "du",
+        about = "Display the size of the trash directories' content"
+    )]
+    Size(ComputeTrashSize),
 
     #[clap(name = "empty", about = "Permanently delete all items in the trash")]
-    Empty,
+    Empty(EmptyTrash),
+
+    #[clap(
+        name = "purge",
+        about = "Permanently delete items that have been in the trash for too long"
+    )]
+    Purge(PurgeItems),
+
+    #[clap(
+        name = "doctor",
+        about = "Check trash directories for inconsistencies and optionally repair them"
+    )]
+    Doctor(CheckTrash),
+
+    #[clap(
+        name = "compact",
+        about = "Remove the trash directories that don't contain any item"
+    )]
+    Compact(CompactTrash),
+
+    #[clap(
+        name = "commit",
+        about = "Move items whose deletion was delayed with 'rm --delay' to the trash once their delay expired"
+    )]
+    Commit(CommitPending),
+
+    #[clap(
+        name = "abort",
+        about = "Cancel deletions delayed with 'rm --delay' and put the items back in place"
+    )]
+    Abort(AbortPending),
+
+    #[clap(
+        name = "serve",
+        about = "Handle requests sent as JSON over a Unix socket, for integration with other tools"
+    )]
+    Serve(ServeRequests),
 }
 
 #[derive(Parser)]
 pub struct ListTrashItems {
     #[clap(long, help = "Only list occurrences of items with a specific name")]
     pub name: Option<String>,
+
+    #[clap(long, help = "Show which user moved each item to the trash")]
+    pub show_owner: bool,
+
+    #[clap(
+        long,
+        help = "List items in the system trash (Recycle Bin, macOS Trash) instead",
+        conflicts_with = "show_owner"
+    )]
+    pub system: bool,
+
+    #[clap(
+        long,
+        help = "Print nothing and exit with a success code only if the trash contains (matching) items",
+        conflicts_with = "system"
+    )]
+    pub check: bool,
+
+    #[clap(
+        long,
+        help = "With --check, exit with a success code only if the trash is empty instead",
+        requires = "check"
+    )]
+    pub empty_ok: bool,
+
+    #[clap(
+        long,
+        help = "Display items in a section per trash directory, with item counts and sizes",
+        conflicts_with_all = ["system", "check"]
+    )]
+    pub grouped: bool,
+
+    #[clap(
+        long,
+        help = "Display items as JSON",
+        conflicts_with_all = ["system", "check", "grouped"]
+    )]
+    pub json: bool,
+
+    #[clap(
+        long,
+        help = "Write the list to a file instead of the standard output",
+        conflicts_with_all = ["system", "check", "grouped"]
+    )]
+    pub output: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "Overwrite the file provided with --output if it already exists",
+        requires = "output"
+    )]
+    pub force: bool,
+
+    #[clap(
+        long,
+        help = "Only list the N most recently trashed items",
+        conflicts_with_all = ["system", "check", "grouped", "oldest"]
+    )]
+    pub newest: Option<usize>,
+
+    #[clap(
+        long,
+        help = "Only list the N least recently trashed items",
+        conflicts_with_all = ["system", "check", "grouped"]
+    )]
+    pub oldest: Option<usize>,
+
+    #[clap(
+        long,
+        help = "Only list items trashed after this date (e.g. '2024-06-30', 'yesterday', '3 days ago')",
+        value_parser = parse_date,
+        allow_hyphen_values = true,
+        conflicts_with_all = ["system", "grouped"]
+    )]
+    pub since: Option<SystemTime>,
+
+    #[clap(
+        long,
+        help = "Only list items trashed before this date (e.g. '2024-06-30', 'today', '-1w')",
+        value_parser = parse_date,
+        allow_hyphen_values = true,
+        conflicts_with_all = ["system", "grouped"]
+    )]
+    pub until: Option<SystemTime>,
+
+    #[clap(
+        long,
+        help = "Only print the number of (matching) items",
+        conflicts_with_all = ["system", "check", "grouped", "json", "output"]
+    )]
+    pub count: bool,
+
+    #[clap(
+        long,
+        help = "Only list items with this tag (can be provided multiple times to require several tags)",
+        conflicts_with_all = ["system", "grouped"]
+    )]
+    pub tag: Vec<String>,
+
+    #[clap(
+        long,
+        help = "Only list items at least this large, directories' size including their content (e.g. '100MiB')",
+        value_parser = parse_size,
+        conflicts_with_all = ["system", "grouped"]
+    )]
+    pub min_size: Option<u64>,
+
+    #[clap(
+        long,
+        help = "Only list items at most this large, directories' size including their content (e.g. '1GB')",
+        value_parser = parse_size,
+        conflicts_with_all = ["system", "grouped"]
+    )]
+    pub max_size: Option<u64>,
+
+    #[clap(long, help = "Show the tags of each item", conflicts_with = "system")]
+    pub show_tags: bool,
+
+    #[clap(
+        long,
+        help = "Show the name of the machine each item was trashed from",
+        conflicts_with = "system"
+    )]
+    pub show_host: bool,
+
+    #[clap(
+        long,
+        help = "Show the path each item was located at before being trashed",
+        conflicts_with = "system"
+    )]
+    pub show_original_path: bool,
+
+    #[clap(
+        long,
+        help = "Only show the last N components of original paths (full paths are kept in JSON)",
+        requires = "show_original_path"
+    )]
+    pub path_depth: Option<usize>,
+
+    #[clap(
+        long,
+        help = "Only list the items of this trash directory (can be provided multiple times)",
+        conflicts_with = "system"
+    )]
+    pub trash_dir: Vec<PathBuf>,
+
+    #[clap(
+        long,
+        help = "Print items as JSON lines as soon as they are read, without sorting them (for very large trashes)",
+        conflicts_with_all = ["system", "check", "grouped", "json", "output", "newest", "oldest", "count"]
+    )]
+    pub stream: bool,
+
+    #[clap(
+        long,
+        help = "Display the content of directory items as a tree, below the table",
+        conflicts_with_all = ["system", "check", "grouped", "json", "stream", "count"]
+    )]
+    pub expand: bool,
+
+    #[clap(
+        long,
+        help = "Only display this many levels of the directories' content with --expand",
+        requires = "expand"
+    )]
+    pub expand_depth: Option<usize>,
 }
 
-#[derive(Parser)]
+#[derive(Parser, Default)]
 pub struct MoveToTrash {
     #[clap(num_args = 1.., help = "Path of the items to move to the trash")]
-    pub paths: Vec<String>,
+    pub paths: Vec<PathBuf>,
 
     #[clap(short, long, help = "Delete the items permanently")]
     pub permanently: bool,
@@ -86,6 +451,120 @@ pub struct MoveToTrash {
         help = "Do not fail when encoutering invalid UTF-8 file names"
     )]
     pub allow_invalid_utf8_item_names: bool,
+
+    #[clap(
+        long,
+        help = "Move the items to the system trash (Recycle Bin, macOS Trash) instead",
+        conflicts_with = "permanently"
+    )]
+    pub system: bool,
+
+    #[clap(
+        long,
+        help = "Use the home directory's trash instead of trash directories located on network filesystems"
+    )]
+    pub no_network_trash: bool,
+
+    #[clap(
+        long,
+        help = "Move the items to the provided trash directory instead of the one associated to their mountpoint",
+        conflicts_with_all = ["permanently", "system", "no_network_trash"]
+    )]
+    pub to_trash_dir: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "Create the trash directory provided with --to-trash-dir if it doesn't exist",
+        requires = "to_trash_dir"
+    )]
+    pub create_trash_dir: bool,
+
+    #[clap(
+        long,
+        help = "Put a copy of the items in the trash and leave the originals in place",
+        conflicts_with_all = ["permanently", "system"]
+    )]
+    pub keep_copy: bool,
+
+    #[clap(
+        long,
+        help = "Move items located on external filesystems to the home directory's trash",
+        conflicts_with_all = ["permanently", "system", "to_trash_dir"]
+    )]
+    pub move_ext_filesystems: bool,
+
+    #[clap(
+        long,
+        value_parser = parse_size,
+        help = "With --move-ext-filesystems, keep items larger than this size (e.g. 500MiB) in their own filesystem's trash",
+        requires = "move_ext_filesystems"
+    )]
+    pub size_limit_move_ext_filesystems: Option<u64>,
+
+    #[clap(
+        long,
+        help = "Flush items and trash directories to the disk after each step, for durability in case of a crash",
+        conflicts_with_all = ["permanently", "system"]
+    )]
+    pub sync: bool,
+
+    #[clap(
+        long,
+        value_parser = parse_duration,
+        help = "Hide the items in place and only move them to the trash after a delay (e.g. 10m) with 'commit', unless cancelled with 'abort'",
+        conflicts_with_all = [
+            "permanently",
+            "system",
+            "keep_copy",
+            "to_trash_dir",
+            "no_network_trash",
+            "move_ext_filesystems",
+            "sync",
+            "one_file_system"
+        ]
+    )]
+    pub delay: Option<Duration>,
+
+    #[clap(
+        long,
+        help = "For directories, move their content to the trash but keep the directories themselves"
+    )]
+    pub contents_only: bool,
+
+    #[clap(
+        long,
+        help = "Refuse to move directories containing items from another filesystem (e.g. mount points) to the trash"
+    )]
+    pub one_file_system: bool,
+
+    #[clap(
+        long = "as",
+        help = "Name to store the item under in the trash, instead of its own (only for a single item)",
+        value_parser = parse_item_name,
+        conflicts_with_all = ["permanently", "system", "delay", "contents_only"]
+    )]
+    pub as_name: Option<String>,
+
+    #[clap(
+        long,
+        help = "Attach a tag to the items in the trash (can be provided multiple times)",
+        conflicts_with_all = ["permanently", "system"]
+    )]
+    pub tag: Vec<String>,
+
+    #[clap(
+        long,
+        help = "Print a JSON description of each item moved to the trash (original path, path in the trash, ID, ...)",
+        conflicts_with_all = ["permanently", "system", "delay"]
+    )]
+    pub json: bool,
+
+    #[clap(
+        short,
+        long,
+        help = "Don't print a summary of the items moved to the trash"
+    )]
+    pub quiet: bool,
 }
 
 #[derive(Parser)]
@@ -93,41 +572,166 @@ pub struct RestoreItem {
     #[clap(help = "Name of the item to restore")]
     pub filename: Option<String>,
 
+    #[clap(long, help = "Destination path (defaults to the current directory)")]
+    pub to: Option<PathBuf>,
+
     #[clap(
         long,
-        help = "Destination path (defaults to the current directory)",
-        requires = "filename"
+        help = "ID of the item to restore (required if multiple items exist with the same name)"
     )]
-    pub to: Option<PathBuf>,
+    pub id: Option<String>,
+
+    #[clap(
+        long,
+        help = "Restore the item from the system trash (Recycle Bin) to its original location",
+        requires = "filename",
+        conflicts_with_all = ["to", "id"]
+    )]
+    pub system: bool,
+
+    #[clap(
+        long,
+        help = "Only print the path the item would be restored to, without restoring it",
+        conflicts_with = "system"
+    )]
+    pub dry_run: bool,
+
+    #[clap(
+        long,
+        help = "Restore all items with the provided name, renaming them in case of collisions",
+        requires = "filename",
+        conflicts_with_all = ["id", "system"]
+    )]
+    pub all_matching: bool,
 
     #[clap(
         long,
-        help = "ID of the item to restore in case multiple exist with the same name",
-        requires = "filename"
+        help = "When restoring a directory over an existing one, merge their content (fails on conflicting files)",
+        conflicts_with_all = ["system", "all_matching"]
+    )]
+    pub merge: bool,
+
+    #[clap(
+        long,
+        help = "Only display this number of items in the interactive picker (most recent first), typing still searches all items",
+        conflicts_with_all = ["filename", "id"]
+    )]
+    pub limit: Option<usize>,
+
+    #[clap(
+        long,
+        help = "Only display items trashed more than this duration ago in the interactive picker (e.g. '1d', '2 weeks')",
+        value_parser = parse_duration,
+        conflicts_with_all = ["filename", "id"]
+    )]
+    pub older_than: Option<Duration>,
+
+    #[clap(
+        long,
+        help = "Only display items trashed less than this duration ago in the interactive picker (e.g. '1h', '30m')",
+        value_parser = parse_duration,
+        conflicts_with_all = ["filename", "id"]
+    )]
+    pub newer_than: Option<Duration>,
+
+    #[clap(
+        long,
+        help = "If multiple items match, print them as JSON and exit with the dedicated exit code instead of prompting",
+        conflicts_with_all = ["system", "all_matching", "limit"]
+    )]
+    pub json: bool,
+
+    #[clap(
+        long,
+        help = "Restore items left in the transfer directory by an interrupted transfer, even if they may be incomplete copies",
+        conflicts_with = "system"
+    )]
+    pub force: bool,
+}
+
+#[derive(Parser)]
+pub struct ExportItem {
+    #[clap(help = "Name of the item to export", required_unless_present = "id")]
+    pub filename: Option<String>,
+
+    #[clap(
+        long,
+        help = "ID of the item to export (required if multiple items exist with the same name)"
     )]
     pub id: Option<String>,
+
+    #[clap(
+        short,
+        long,
+        help = "Path of the archive to create, its format being determined by its extension (.tar, .tar.gz or .tgz)"
+    )]
+    pub output: PathBuf,
+
+    #[clap(long, help = "Overwrite the archive if it already exists")]
+    pub force: bool,
 }
 
 #[derive(Parser)]
+#[clap(group = clap::ArgGroup::new("bulk_drop").args(["older_than", "matching"]).multiple(true))]
 pub struct DropItem {
-    #[clap(help = "Name of the item to permanently delete from the trash")]
-    pub filename: String,
+    #[clap(
+        help = "Name of the item to permanently delete from the trash",
+        required_unless_present_any = ["id", "older_than", "matching"]
+    )]
+    pub filename: Option<String>,
 
     #[clap(
         long,
-        help = "ID of the item to drop in case multiple exist with the same name"
+        help = "ID of the item to drop (required if multiple items exist with the same name)"
     )]
     pub id: Option<String>,
+
+    #[clap(
+        long,
+        help = "Drop all items trashed more than this duration ago (e.g. '30d', '2 weeks')",
+        value_parser = parse_duration,
+        conflicts_with_all = ["filename", "id"]
+    )]
+    pub older_than: Option<Duration>,
+
+    #[clap(
+        long,
+        help = "Drop all items whose name contains this value (can be combined with --older-than)",
+        conflicts_with_all = ["filename", "id"]
+    )]
+    pub matching: Option<String>,
+
+    #[clap(
+        long,
+        help = "When dropping multiple items, ask before dropping each one",
+        requires = "bulk_drop"
+    )]
+    pub confirm_each: bool,
+
+    #[clap(
+        long,
+        help = "Only look for the item in this trash directory (can be provided multiple times)"
+    )]
+    pub trash_dir: Vec<PathBuf>,
+
+    #[clap(
+        long,
+        help = "If multiple items match, print them as JSON and exit with the dedicated exit code instead of prompting"
+    )]
+    pub json: bool,
 }
 
 #[derive(Parser)]
 pub struct GetItemPath {
-    #[clap(help = "Name of the item to get the path of in the trash")]
-    pub filename: String,
+    #[clap(
+        help = "Name of the item to get the path of in the trash",
+        required_unless_present = "id"
+    )]
+    pub filename: Option<String>,
 
     #[clap(
         long,
-        help = "ID of the item to get in case multiple exist with the same name"
+        help = "ID of the item to get (required if multiple items exist with the same name)"
     )]
     pub id: Option<String>,
 
@@ -137,4 +741,264 @@ pub struct GetItemPath {
         help = "Do not fail if the path contains invalid UTF-8 characters"
     )]
     pub allow_invalid_utf8_path: bool,
+
+    #[clap(long, help = "Only print the item's ID instead of its path")]
+    pub id_only: bool,
+
+    #[clap(
+        long,
+        help = "Print a line following this template instead of the path, with '{path}', '{name}', '{id}', '{date}' and '{size}' placeholders",
+        value_parser = parse_item_template,
+        conflicts_with = "id_only"
+    )]
+    pub format: Option<String>,
+}
+
+#[derive(Parser)]
+pub struct GetItemInfos {
+    #[clap(
+        help = "Name of the item to get informations about",
+        required_unless_present = "id"
+    )]
+    pub filename: Option<String>,
+
+    #[clap(
+        long,
+        help = "ID of the item to get (required if multiple items exist with the same name)"
+    )]
+    pub id: Option<String>,
+}
+
+#[derive(Parser)]
+pub struct PurgeItems {
+    #[clap(
+        long,
+        help = "Delete items trashed more than this duration ago (e.g. '30d', '12h', '1w2d', '2 weeks')",
+        value_parser = parse_duration,
+        required_unless_present = "apply_policy",
+        conflicts_with = "apply_policy"
+    )]
+    pub older_than: Option<Duration>,
+
+    #[clap(
+        long,
+        help = "Apply the per-filesystem rules of the retention policy file"
+    )]
+    pub apply_policy: bool,
+
+    #[clap(
+        long,
+        help = "Path to the retention policy file (defaults to 'trasher/retention.conf' in the configuration directory)",
+        requires = "apply_policy"
+    )]
+    pub policy_file: Option<PathBuf>,
+
+    #[clap(long, help = "Keep running and purge the trash periodically")]
+    pub watch: bool,
+
+    #[clap(
+        long,
+        help = "Interval between two purges in watch mode",
+        default_value = "1h",
+        value_parser = parse_duration,
+        requires = "watch"
+    )]
+    pub interval: Duration,
+}
+
+#[derive(Parser)]
+pub struct GetTrashPath {
+    #[clap(
+        help = "Get the trash directory of this path instead of the current directory (it doesn't need to exist)",
+        conflicts_with = "all"
+    )]
+    pub path: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "Print the path of every existing trash directory on the system"
+    )]
+    pub all: bool,
+
+    #[clap(
+        long,
+        help = "With --all, also print the trash directories that don't exist yet",
+        requires = "all"
+    )]
+    pub include_potential: bool,
+}
+
+#[derive(Parser)]
+pub struct EmptyTrash {
+    #[clap(
+        long,
+        help = "List the items of each trash directory before asking for confirmation"
+    )]
+    pub show_items: bool,
+
+    #[clap(
+        long,
+        help = "Display a summary of the removed items and freed space once done"
+    )]
+    pub stats: bool,
+
+    #[clap(
+        long,
+        help = "For large trashes, require typing the trash directory's name instead of 'y' to confirm"
+    )]
+    pub confirm_name: bool,
+
+    #[clap(
+        long,
+        help = "Number of items above which --confirm-name requires typing the trash directory's name",
+        default_value_t = 100,
+        requires = "confirm_name"
+    )]
+    pub confirm_name_threshold: usize,
+}
+
+#[derive(Parser)]
+pub struct CommitPending {
+    #[clap(long, help = "Also commit deletions whose delay has not expired yet")]
+    pub all: bool,
+}
+
+#[derive(Parser)]
+pub struct AbortPending {
+    #[clap(help = "Only cancel the deletion of items with this name")]
+    pub name: Option<String>,
+}
+
+#[derive(Parser)]
+pub struct ServeRequests {
+    #[clap(long, help = "Path of the Unix socket to listen on")]
+    pub socket: PathBuf,
+}
+
+#[derive(Parser)]
+pub struct FindItems {
+    #[clap(help = "Words to search in the items' names")]
+    pub query: String,
+
+    #[clap(long, value_enum, default_value_t, help = "Order of the results")]
+    pub sort: FindSort,
+
+    #[clap(long, help = "Display the score of each item")]
+    pub show_score: bool,
+}
+
+/// Order of the results of `find`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum FindSort {
+    /// Best matches first
+    #[default]
+    Score,
+
+    /// Most recently trashed items first
+    Date,
+
+    /// Largest items first
+    Size,
+}
+
+#[derive(Parser)]
+pub struct DecodeTrashFilename {
+    #[clap(help = "Name of the item in the trash directory (e.g. 'notes.txt ^AThGtKA41lQ')")]
+    pub trash_filename: OsString,
+
+    #[clap(
+        long,
+        help = "Time zone to display the deletion date in: 'UTC', 'local' or an IANA name like 'Europe/Paris' (defaults to local)",
+        value_parser = parse_time_zone
+    )]
+    pub tz: Option<TimeZone>,
+}
+
+#[derive(Parser)]
+pub struct ComputeTrashSize {
+    #[clap(
+        long,
+        help = "Only print the total size of all trash directories, in bytes"
+    )]
+    pub total_only: bool,
+
+    #[clap(
+        long,
+        help = "With --total-only, print a human-readable size instead of bytes",
+        requires = "total_only"
+    )]
+    pub human: bool,
+}
+
+#[derive(Parser)]
+pub struct CompactTrash {
+    #[clap(long, help = "Only print the trash directories that would be removed")]
+    pub dry_run: bool,
+}
+
+#[derive(Parser)]
+pub struct CheckTrash {
+    #[clap(long, help = "Repair the detected inconsistencies")]
+    pub fix: bool,
+}
+
+fn parse_glob(pattern: &str) -> Result<Glob, String> {
+    Glob::new(pattern).map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+impl Config {
+    /// Parse the configuration from global arguments, only using the provided trash directory
+    pub fn for_tests(trash_root: &std::path::Path, args: &[&str]) -> Self {
+        let mut all_args = vec![
+            OsString::from("trasher"),
+            OsString::from("--trash-root"),
+            trash_root.as_os_str().to_owned(),
+        ];
+
+        all_args.extend(args.iter().map(OsString::from));
+        all_args.push(OsString::from("ls"));
+
+        Opts::parse_from(all_args).config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+
+    #[test]
+    fn arguments_are_consistent() {
+        Opts::command().debug_assert();
+    }
+
+    #[test]
+    fn ignore_case_short_flag() {
+        let opts = Opts::parse_from(["trasher", "rm", "-i", "-I", "file"]);
+        assert!(opts.config.ignore_case);
+
+        let Action::Remove(action) = opts.action else {
+            panic!("expected the 'rm' action");
+        };
+
+        assert!(action.ignore);
+    }
+
+    #[test]
+    fn delay_conflicts_with_unsaved_options() {
+        for flag in [
+            "--no-network-trash",
+            "--move-ext-filesystems",
+            "--sync",
+            "--one-file-system",
+            "--keep-copy",
+        ] {
+            assert!(
+                Opts::try_parse_from(["trasher", "rm", "--delay", "10m", flag, "file"]).is_err(),
+                "'--delay' should conflict with '{flag}'"
+            );
+        }
+    }
 }
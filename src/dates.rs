@@ -0,0 +1,248 @@
+use std::time::{Duration, SystemTime};
+
+use jiff::{civil::Date, tz::TimeZone, Timestamp, Zoned};
+
+/// Units accepted in durations, with their length in seconds
+static DURATION_UNITS: &[(&[&str], u64)] = &[
+    (&["s", "sec", "secs", "second", "seconds"], 1),
+    (&["m", "min", "mins", "minute", "minutes"], 60),
+    (&["h", "hr", "hrs", "hour", "hours"], 60 * 60),
+    (&["d", "day", "days"], 24 * 60 * 60),
+    (&["w", "week", "weeks"], 7 * 24 * 60 * 60),
+];
+
+/// Parse a duration made of one or more `<number><unit>` parts (e.g. `30d`, `1h30m`, `2 weeks`)
+///
+/// Supported units are `s` (seconds), `m` (minutes), `h` (hours), `d` (days) and `w` (weeks),
+/// which can also be written in full (e.g. `3 days`, `1 hour 30 minutes`)
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+
+    if input.is_empty() {
+        return Err("Duration cannot be empty".to_owned());
+    }
+
+    let mut total = 0u64;
+    let mut rest = input;
+
+    while !rest.is_empty() {
+        let digits_len = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(|| format!("Missing unit after number in duration '{input}'"))?;
+
+        if digits_len == 0 {
+            return Err(format!("Expected a number in duration '{input}'"));
+        }
+
+        let value = rest[..digits_len]
+            .parse::<u64>()
+            .map_err(|err| format!("Invalid number in duration '{input}': {err}"))?;
+
+        rest = rest[digits_len..].trim_start();
+
+        let unit_len = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+
+        let unit = &rest[..unit_len];
+
+        let (_, secs) = DURATION_UNITS
+            .iter()
+            .find(|(names, _)| names.contains(&unit))
+            .ok_or_else(|| format!("Unknown unit '{unit}' in duration '{input}'"))?;
+
+        total = value
+            .checked_mul(*secs)
+            .and_then(|secs| total.checked_add(secs))
+            .ok_or_else(|| format!("Duration '{input}' is too large"))?;
+
+        rest = rest[unit_len..].trim_start();
+    }
+
+    Ok(Duration::from_secs(total))
+}
+
+/// Parse a point in time, either absolute or relative to now
+///
+/// Supported forms are:
+///
+/// * `now`, `today` (start of the current day) and `yesterday` (start of the previous day)
+/// * a duration in the past, like `3d`, `-3 days` or `3 days ago`
+/// * a date (`2024-06-30`, at the start of the day) or an RFC 3339 timestamp (`2024-06-30T12:00:00Z`)
+pub fn parse_date(input: &str) -> Result<SystemTime, String> {
+    let input = input.trim();
+
+    match input {
+        "now" => return Ok(SystemTime::now()),
+        "today" => return start_of_day(0),
+        "yesterday" => return start_of_day(1),
+        _ => {}
+    }
+
+    if let Ok(timestamp) = input.parse::<Timestamp>() {
+        return Ok(SystemTime::from(timestamp));
+    }
+
+    if let Ok(date) = input.parse::<Date>() {
+        return date
+            .to_zoned(TimeZone::system())
+            .map(|date| SystemTime::from(date.timestamp()))
+            .map_err(|err| format!("Invalid date '{input}': {err}"));
+    }
+
+    let ago = input.strip_prefix('-').unwrap_or(input);
+    let ago = ago.strip_suffix("ago").unwrap_or(ago);
+
+    let duration = parse_duration(ago).map_err(|err| {
+        format!("{err}\n\nExpected 'now', 'today', 'yesterday', a date, an RFC 3339 timestamp or a duration in the past (e.g. '3 days ago')")
+    })?;
+
+    SystemTime::now()
+        .checked_sub(duration)
+        .ok_or_else(|| format!("Date '{input}' is too far in the past"))
+}
+
+/// Parse a time zone name, either `UTC`, `local` or an IANA name (e.g. `Europe/Paris`)
+pub fn parse_time_zone(input: &str) -> Result<TimeZone, String> {
+    match input {
+        "UTC" | "utc" => Ok(TimeZone::UTC),
+        "local" => Ok(TimeZone::system()),
+        _ => TimeZone::get(input).map_err(|err| format!("Unknown time zone '{input}': {err}")),
+    }
+}
+
+/// Describe how long ago a point in time was, in its largest unit (e.g. `3 days ago`)
+pub fn format_age(time: SystemTime) -> String {
+    let secs = SystemTime::now()
+        .duration_since(time)
+        .unwrap_or_default()
+        .as_secs();
+
+    let (names, unit_secs) = DURATION_UNITS
+        .iter()
+        .rev()
+        .find(|(_, unit_secs)| secs >= *unit_secs)
+        .unwrap_or(&DURATION_UNITS[0]);
+
+    let count = secs / unit_secs;
+
+    // The last name of each unit is its plural form, the one before its singular form
+    let name = names[names.len() - if count == 1 { 2 } else { 1 }];
+
+    format!("{count} {name} ago")
+}
+
+/// Get the start of the current day, minus the provided number of days
+fn start_of_day(days_ago: i64) -> Result<SystemTime, String> {
+    Zoned::now()
+        .start_of_day()
+        .and_then(|date| date.checked_sub(jiff::Span::new().days(days_ago)))
+        .map(|date| SystemTime::from(date.timestamp()))
+        .map_err(|err| format!("Failed to compute date: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: u64 = 60;
+    const HOUR: u64 = 60 * MINUTE;
+    const DAY: u64 = 24 * HOUR;
+
+    /// Check that a parsed date is the provided duration before now (with some leeway for the test's run time)
+    fn assert_ago(input: &str, expected: Duration) {
+        let parsed = parse_date(input).unwrap();
+        let ago = SystemTime::now().duration_since(parsed).unwrap();
+
+        assert!(
+            ago >= expected && ago - expected < Duration::from_secs(5),
+            "'{input}' was parsed as {ago:?} ago instead of {expected:?}"
+        );
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(5 * MINUTE)));
+        assert_eq!(parse_duration("30d"), Ok(Duration::from_secs(30 * DAY)));
+        assert_eq!(
+            parse_duration("1h30m"),
+            Ok(Duration::from_secs(HOUR + 30 * MINUTE))
+        );
+        assert_eq!(
+            parse_duration("1 hour 30 minutes"),
+            Ok(Duration::from_secs(HOUR + 30 * MINUTE))
+        );
+        assert_eq!(parse_duration("2 weeks"), Ok(Duration::from_secs(14 * DAY)));
+        assert_eq!(parse_duration(" 1 day "), Ok(Duration::from_secs(DAY)));
+    }
+
+    #[test]
+    fn invalid_durations() {
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("3").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("3 parsecs").is_err());
+        assert!(parse_duration("1h 30").is_err());
+        assert!(parse_duration("-3d").is_err());
+        assert!(parse_duration("99999999999999999999w").is_err());
+    }
+
+    #[test]
+    fn relative_dates() {
+        assert_ago("now", Duration::ZERO);
+        assert_ago("3d", Duration::from_secs(3 * DAY));
+        assert_ago("-3 days", Duration::from_secs(3 * DAY));
+        assert_ago("3 days ago", Duration::from_secs(3 * DAY));
+        assert_ago("1h30m ago", Duration::from_secs(HOUR + 30 * MINUTE));
+        assert_ago("2 weeks ago", Duration::from_secs(14 * DAY));
+    }
+
+    #[test]
+    fn day_starts() {
+        let today = parse_date("today").unwrap();
+        let yesterday = parse_date("yesterday").unwrap();
+
+        assert!(today <= SystemTime::now());
+        assert!(SystemTime::now().duration_since(today).unwrap() <= Duration::from_secs(DAY));
+
+        // Days may be 23 or 25 hours long when switching to or from daylight saving time
+        let day = today.duration_since(yesterday).unwrap();
+        assert!(day >= Duration::from_secs(23 * HOUR) && day <= Duration::from_secs(25 * HOUR));
+    }
+
+    #[test]
+    fn absolute_dates() {
+        assert_eq!(
+            parse_date("2024-06-30T12:00:00Z"),
+            Ok(SystemTime::from(
+                "2024-06-30T12:00:00Z".parse::<Timestamp>().unwrap()
+            ))
+        );
+
+        assert_eq!(
+            parse_date("2024-06-30T14:00:00+02:00"),
+            parse_date("2024-06-30T12:00:00Z")
+        );
+
+        let expected = "2024-06-30"
+            .parse::<Date>()
+            .unwrap()
+            .to_zoned(TimeZone::system())
+            .unwrap();
+
+        assert_eq!(
+            parse_date("2024-06-30"),
+            Ok(SystemTime::from(expected.timestamp()))
+        );
+    }
+
+    #[test]
+    fn invalid_dates() {
+        assert!(parse_date("").is_err());
+        assert!(parse_date("abc").is_err());
+        assert!(parse_date("tomorrow").is_err());
+        assert!(parse_date("2024-13-01").is_err());
+        assert!(parse_date("3 parsecs ago").is_err());
+    }
+}
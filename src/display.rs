@@ -1,3 +1,14 @@
+use std::{
+    cell::Cell,
+    io::{self, IsTerminal},
+    sync::atomic::Ordering,
+};
+
+use anyhow::{bail, Context, Result};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+use crate::{Config, HIDE_PROGRESS};
+
 #[macro_export]
 macro_rules! _format {
     ($color: ident => $message: tt, $($params: tt)*) => {{
@@ -51,3 +62,135 @@ macro_rules! success {
         success!($message,)
     }};
 }
+
+/// Fail if confirmations can't be asked to the user (see [`Config::non_interactive`])
+fn ensure_interactive(question: &str, config: &Config) -> Result<()> {
+    if config.non_interactive {
+        bail!("Confirmation required but it can't be asked here: {question}");
+    }
+
+    Ok(())
+}
+
+/// Ask the user to confirm an operation, unless the global `--assume-yes` flag was provided
+pub fn confirm(question: &str, config: &Config) -> Result<bool> {
+    if config.assume_yes {
+        return Ok(true);
+    }
+
+    ensure_interactive(question, config)?;
+
+    warn!("{question} [y/N]?");
+
+    let mut answer = String::new();
+
+    io::stdin()
+        .read_line(&mut answer)
+        .context("Failed to get user confirmation")?;
+
+    Ok(answer.trim().eq_ignore_ascii_case("y"))
+}
+
+/// Answer to a confirmation asked for each item of a bulk operation
+pub enum EachAnswer {
+    Yes,
+    No,
+    All,
+    Quit,
+}
+
+/// Ask the user to confirm an operation on one item out of many, unless the global `--assume-yes` flag was provided
+pub fn confirm_each_item(question: &str, config: &Config) -> Result<EachAnswer> {
+    if config.assume_yes {
+        return Ok(EachAnswer::All);
+    }
+
+    ensure_interactive(question, config)?;
+
+    loop {
+        warn!("{question} [y/n/a(ll)/q(uit)]?");
+
+        let mut answer = String::new();
+
+        io::stdin()
+            .read_line(&mut answer)
+            .context("Failed to get user confirmation")?;
+
+        match answer.trim().to_ascii_lowercase().as_str() {
+            "y" | "yes" => return Ok(EachAnswer::Yes),
+            "n" | "no" => return Ok(EachAnswer::No),
+            "a" | "all" => return Ok(EachAnswer::All),
+            // An empty answer (e.g. end of input) must not delete anything else
+            "q" | "quit" | "" => return Ok(EachAnswer::Quit),
+            _ => continue,
+        }
+    }
+}
+
+/// Ask the user to confirm an operation by typing a name, unless the global `--assume-yes` flag was provided
+pub fn confirm_typing(question: &str, expected: &str, config: &Config) -> Result<bool> {
+    if config.assume_yes {
+        return Ok(true);
+    }
+
+    ensure_interactive(question, config)?;
+
+    warn!("{question}, type '{expected}' to confirm:");
+
+    let mut answer = String::new();
+
+    io::stdin()
+        .read_line(&mut answer)
+        .context("Failed to get user confirmation")?;
+
+    Ok(answer.trim() == expected)
+}
+
+/// Progress reporting for non-interactive consumers (e.g. a GUI wrapping Trasher)
+///
+/// When the standard error isn't a terminal, progress bars are not displayed, so the progress is
+/// printed as `PROGRESS <percent>%` lines instead, each time the percentage changes.
+pub struct PercentProgress {
+    enabled: bool,
+    last: Cell<Option<u64>>,
+}
+
+impl PercentProgress {
+    pub fn new() -> Self {
+        Self {
+            enabled: !io::stderr().is_terminal() && !HIDE_PROGRESS.load(Ordering::SeqCst),
+            last: Cell::new(None),
+        }
+    }
+
+    pub fn update(&self, done: u64, total: u64) {
+        if !self.enabled {
+            return;
+        }
+
+        let percent = (done.min(total) * 100).checked_div(total).unwrap_or(100);
+
+        if self.last.get() != Some(percent) {
+            self.last.set(Some(percent));
+            eprintln!("PROGRESS {percent}%");
+        }
+    }
+}
+
+/// Create a progress bar with the provided template, hidden if the global `--no-progress` flag was provided
+pub fn progress_bar(len: u64, template: &str) -> ProgressBar {
+    let pbr = ProgressBar::new(len);
+
+    pbr.set_style(
+        ProgressStyle::default_bar()
+            .template(template)
+            .expect("Invalid progress bar template")
+            .progress_chars("#>-"),
+    );
+
+    if HIDE_PROGRESS.load(Ordering::SeqCst) {
+        pbr.set_draw_target(ProgressDrawTarget::hidden());
+    }
+
+    pbr
+}
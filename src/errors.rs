@@ -0,0 +1,135 @@
+use std::{fmt, io, path::PathBuf, process::ExitCode};
+
+use crate::fsutils::{table_for_items, TableOptions, TrashedItem};
+
+/// Exit code used for failures that don't fall into a more specific category
+pub const EXIT_GENERIC_FAILURE: u8 = 1;
+
+// Exit code 2 is used by the arguments parser for usage errors, so specific failures start at 3
+
+/// Exit code used when the requested item could not be found in the trash
+pub const EXIT_ITEM_NOT_FOUND: u8 = 3;
+
+/// Exit code used when multiple trash items match and no ID was provided
+pub const EXIT_AMBIGUOUS_MATCH: u8 = 4;
+
+/// Exit code used when a filesystem operation was denied
+pub const EXIT_PERMISSION_DENIED: u8 = 5;
+
+/// Exit code used when the target of an operation already exists
+pub const EXIT_TARGET_EXISTS: u8 = 6;
+
+/// Failures scripts may want to branch on, each one mapped to a dedicated exit code
+#[derive(Debug)]
+pub enum TrasherError {
+    ItemNotFound,
+    NoItemWithId,
+    AmbiguousMatch(Vec<TrashedItem>),
+    AmbiguousMatchPrinted,
+    TargetExists(PathBuf),
+}
+
+impl TrasherError {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::ItemNotFound | Self::NoItemWithId => EXIT_ITEM_NOT_FOUND,
+            Self::AmbiguousMatch(_) | Self::AmbiguousMatchPrinted => EXIT_AMBIGUOUS_MATCH,
+            Self::TargetExists(_) => EXIT_TARGET_EXISTS,
+        }
+    }
+}
+
+impl fmt::Display for TrasherError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ItemNotFound => write!(f, "Specified item was not found in the trash."),
+            Self::NoItemWithId => write!(f, "There is no trash item with the provided ID"),
+            Self::AmbiguousMatch(candidates) => write!(
+                f,
+                "Multiple items with this filename were found in the trash:\n\n{}",
+                table_for_items(candidates, &TableOptions::default())
+            ),
+            Self::AmbiguousMatchPrinted => write!(
+                f,
+                "Multiple items with this filename were found in the trash, candidates were printed as JSON"
+            ),
+            Self::TargetExists(path) => {
+                write!(f, "Target path already exists: {}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for TrasherError {}
+
+/// Determine the exit code to use for an error by inspecting its whole chain
+pub fn exit_code_for(err: &anyhow::Error) -> ExitCode {
+    for cause in err.chain() {
+        if let Some(err) = cause.downcast_ref::<TrasherError>() {
+            return ExitCode::from(err.exit_code());
+        }
+
+        let permission_denied = match cause.downcast_ref::<io::Error>() {
+            Some(err) => err.kind() == io::ErrorKind::PermissionDenied,
+            None => matches!(
+                cause.downcast_ref::<fs_extra::error::Error>(),
+                Some(err) if matches!(err.kind, fs_extra::error::ErrorKind::PermissionDenied)
+            ),
+        };
+
+        if permission_denied {
+            return ExitCode::from(EXIT_PERMISSION_DENIED);
+        }
+    }
+
+    ExitCode::from(EXIT_GENERIC_FAILURE)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{error::ErrorKind, CommandFactory};
+
+    use super::*;
+    use crate::args::Opts;
+
+    #[test]
+    fn exit_codes_do_not_clash_with_usage_errors() {
+        let usage_error = Opts::command()
+            .error(ErrorKind::UnknownArgument, "")
+            .exit_code();
+
+        let codes = [
+            EXIT_GENERIC_FAILURE,
+            EXIT_ITEM_NOT_FOUND,
+            EXIT_AMBIGUOUS_MATCH,
+            EXIT_PERMISSION_DENIED,
+            EXIT_TARGET_EXISTS,
+        ];
+
+        for (i, code) in codes.iter().enumerate() {
+            assert_ne!(i32::from(*code), usage_error);
+            assert!(!codes[i + 1..].contains(code));
+        }
+    }
+
+    #[test]
+    fn exit_codes_are_listed_in_help() {
+        let help = Opts::command().render_long_help().to_string();
+
+        for code in [
+            EXIT_ITEM_NOT_FOUND,
+            EXIT_AMBIGUOUS_MATCH,
+            EXIT_PERMISSION_DENIED,
+            EXIT_TARGET_EXISTS,
+        ] {
+            assert!(help.contains(&format!("  {code}  ")));
+        }
+    }
+
+    #[test]
+    fn exit_code_is_found_in_error_chain() {
+        let err = anyhow::Error::new(TrasherError::ItemNotFound).context("Failed to restore item");
+
+        assert_eq!(exit_code_for(&err), ExitCode::from(EXIT_ITEM_NOT_FOUND));
+    }
+}
@@ -1,31 +1,78 @@
 use std::{
     cell::RefCell,
-    collections::BTreeSet,
+    collections::{BTreeSet, HashMap},
     ffi::OsStr,
-    fs,
+    fs::{self, Metadata},
+    io::{self, IsTerminal},
     path::Component,
     path::{Path, PathBuf},
     rc::Rc,
+    sync::{LazyLock, Mutex, PoisonError},
+    time::{Duration, SystemTime},
 };
 
-use anyhow::{bail, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use comfy_table::{presets::UTF8_FULL_CONDENSED, ContentArrangement, Table};
 use fs_extra::dir::TransitProcessResult;
-use indicatif::{ProgressBar, ProgressStyle};
-use jiff::Zoned;
+use globset::{GlobSet, GlobSetBuilder};
+use jiff::{tz::TimeZone, Timestamp, Zoned};
 use mountpoints::mountpaths;
+use serde::Serialize;
 use walkdir::WalkDir;
 
-use crate::{debug, error, Config};
+use crate::{
+    debug,
+    display::{progress_bar, PercentProgress},
+    error,
+    errors::TrasherError,
+    fuzzy::{run_fuzzy_finder, FuzzyFinderItem},
+    warn, Config,
+};
 
-use super::items::TrashItemInfos;
+use super::items::{ItemOwner, TrashItemInfos, TrashItemMetadata, METADATA_VERSION};
 
 /// Name of the trash directory
 const TRASH_DIR_NAME: &str = ".trasher";
 
+/// Name of the home directory's trash when following the XDG Base Directory specification
+const XDG_TRASH_DIR_NAME: &str = "trash";
+
 /// Name of the transfer directory in the trash
 pub const TRASH_TRANSFER_DIRNAME: &str = ".#PARTIAL";
 
+/// Name of the directory containing the items' metadata in the trash
+pub const TRASH_METADATA_DIRNAME: &str = ".#METADATA";
+
+/// Name of the directory leftover partial transfers that may be incomplete are moved to by 'trasher doctor --fix'
+pub const TRASH_QUARANTINE_DIRNAME: &str = ".#INCOMPLETE";
+
+/// Name of the file explaining how to recover items, written in new trash directories
+pub const TRASH_RECOVERY_FILENAME: &str = "RECOVERY.txt";
+
+/// Content of the recovery file
+const TRASH_RECOVERY_NOTE: &str = "\
+This directory is a trash managed by Trasher (https://github.com/ClementNerma/Trasher).
+
+Each item is stored under its original name, followed by a space, a '^' and an ID
+encoding the date it was trashed at (e.g. 'notes.txt ^AThGtKA41lQ'). To recover an item
+manually, move it out of this directory and remove the ' ^<ID>' suffix from its name.
+
+With Trasher installed, use 'trasher ls' to list the trashed items and 'trasher unrm <name>'
+to restore one.
+
+Other entries are used internally:
+
+* '.#METADATA' contains additional information about the items (owner, tags, ...)
+* '.#PARTIAL' contains items being moved from another filesystem, which may be incomplete
+* '.#INCOMPLETE' contains leftover partial transfers which may be incomplete copies
+";
+
+/// Prefix of the temporary items created while restoring across filesystems
+pub const RESTORE_TRANSFER_PREFIX: &str = ".#RESTORING ";
+
+/// Delimiter between a filename and an ID when both are provided as a single argument
+pub const ITEM_ID_DELIMITER: char = '@';
+
 /// Directories to never create a trash directory for
 pub static ALWAYS_EXCLUDE_DIRS: &[&str] = &[
     "/bin",
@@ -41,26 +88,193 @@ pub static ALWAYS_EXCLUDE_DIRS: &[&str] = &[
     "/var/lib/docker",
 ];
 
-/// Determine path to the trash directory for a given item and create it if required
-pub fn determine_trash_dir_for(item: &Path, config: &Config) -> Result<PathBuf> {
-    debug!("Determining trasher directory for item: {}", item.display());
+/// Filesystem types that are accessed through the network
+static NETWORK_FILESYSTEM_TYPES: &[&str] = &[
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smbfs",
+    "smb3",
+    "afs",
+    "ceph",
+    "9p",
+    "fuse.sshfs",
+    "fuse.glusterfs",
+    "fuse.s3fs",
+    "fuse.rclone",
+    "davfs",
+];
+
+/// Get the path to the trash directory located in the user's home directory
+///
+/// With `--xdg-home-trash`, it's located in the data directory of the XDG Base Directory specification instead.
+pub fn home_trash_dir(config: &Config) -> Result<PathBuf> {
+    if let Some(trash_root) = trash_root(config)? {
+        return Ok(trash_root);
+    }
+
+    if !config.xdg_home_trash {
+        return Ok(canonical_home_dir()?.join(TRASH_DIR_NAME));
+    }
+
+    // Relative paths must be ignored as per the specification
+    let data_dir = match std::env::var_os("XDG_DATA_HOME").map(PathBuf::from) {
+        Some(data_dir) if data_dir.is_absolute() => data_dir,
+        _ => canonical_home_dir()?.join(".local").join("share"),
+    };
+
+    // The 'trasher' directory is shared with other data (e.g. the pending deletions on Linux)
+    lenient_absolute_path(&data_dir.join("trasher").join(XDG_TRASH_DIR_NAME))
+}
+
+/// Get the trash directory provided with `--trash-root`, which replaces all other trash directories
+fn trash_root(config: &Config) -> Result<Option<PathBuf>> {
+    config
+        .trash_root
+        .as_deref()
+        .map(lenient_absolute_path)
+        .transpose()
+}
+
+/// Get the trash directory to use for all items, if one is forced by `--trash-root` or `--single-trash`
+fn forced_trash_dir(config: &Config) -> Result<Option<PathBuf>> {
+    if let Some(trash_root) = trash_root(config)? {
+        return Ok(Some(trash_root));
+    }
+
+    if config.single_trash {
+        return home_trash_dir(config).map(Some);
+    }
+
+    Ok(None)
+}
 
+/// Get the path to the user's home directory, canonicalized if possible
+///
+/// This ensures a symlinked home directory (e.g. `/home` -> `/export/home`) can be
+/// compared to the canonicalized mountpoints and item paths.
+fn canonical_home_dir() -> Result<PathBuf> {
     let home_dir = dirs::home_dir().context("Failed to determine path to user's home directory")?;
 
+    Ok(fs::canonicalize(&home_dir).unwrap_or(home_dir))
+}
+
+/// Get the type of the filesystem an item is located on
+///
+/// Read from `/proc/mounts` on Linux and from the system's mount information elsewhere,
+/// returns `None` when the type can't be determined
+pub fn filesystem_type(path: &Path) -> Option<String> {
+    #[cfg(target_os = "linux")]
+    {
+        let path = fs::canonicalize(path).ok()?;
+        let mounts = fs::read_to_string("/proc/mounts").ok()?;
+
+        mounts
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split(' ');
+                let mountpoint = unescape_mount_field(fields.nth(1)?);
+                let fstype = fields.next()?;
+                Some((PathBuf::from(mountpoint), fstype))
+            })
+            .filter(|(mountpoint, _)| path.starts_with(mountpoint))
+            // When multiple filesystems are mounted on the same path, the last one is the visible one
+            .max_by_key(|(mountpoint, _)| mountpoint.components().count())
+            .map(|(_, fstype)| fstype.to_owned())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let path = fs::canonicalize(path).ok()?;
+
+        mountpoints::mountinfos()
+            .ok()?
+            .into_iter()
+            .filter(|info| path.starts_with(&info.path))
+            .max_by_key(|info| info.path.components().count())
+            .and_then(|info| info.format)
+    }
+}
+
+/// Decode the octal escapes (e.g. `\040` for spaces) used in `/proc/mounts`
+#[cfg(target_os = "linux")]
+fn unescape_mount_field(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    let mut rest = field;
+
+    while let Some(pos) = rest.find('\\') {
+        out.push_str(&rest[..pos]);
+
+        match rest
+            .get(pos + 1..pos + 4)
+            .and_then(|code| u8::from_str_radix(code, 8).ok())
+        {
+            Some(byte) => {
+                out.push(char::from(byte));
+                rest = &rest[pos + 4..];
+            }
+            None => {
+                out.push('\\');
+                rest = &rest[pos + 1..];
+            }
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+/// Check if a filesystem type is a network one
+pub fn is_network_filesystem(fstype: &str) -> bool {
+    NETWORK_FILESYSTEM_TYPES.contains(&fstype)
+}
+
+/// Read a file listing directories to exclude, one per line
+///
+/// Blank lines and lines starting with a `#` are ignored. Relative paths are resolved from the file's directory.
+fn read_exclusions_file(path: &Path) -> Result<Vec<PathBuf>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read exclusions file at path: {}", path.display()))?;
+
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| base_dir.join(line))
+        .collect())
+}
+
+/// Compute the list of directories to never create a trash directory for
+///
+/// Directories that don't exist (e.g. on a drive that isn't mounted) are kept as well,
+/// so the exclusion applies as soon as they appear.
+pub fn compute_exclusions(config: &Config) -> Result<Vec<PathBuf>> {
+    let excluded_from_files = config
+        .exclude_from
+        .iter()
+        .map(|path| read_exclusions_file(path))
+        .collect::<Result<Vec<_>, _>>()?;
+
     let mut exclude = config
         .exclude
         .iter()
-        .filter_map(|dir| {
-            if !dir.is_dir() {
-                None
-            } else {
-                Some(fs::canonicalize(dir).with_context(|| {
-                    format!(
-                        "Failed to canonicalize excluded directory: {}",
-                        dir.display()
-                    )
-                }))
+        .chain(excluded_from_files.iter().flatten())
+        .map(|dir| {
+            if fs::symlink_metadata(dir).is_err() {
+                // Resolve the path from its nearest existing ancestor, like the canonicalized item paths it's compared to
+                return lenient_absolute_path(dir).with_context(|| {
+                    format!("Failed to resolve excluded directory: {}", dir.display())
+                });
             }
+
+            fs::canonicalize(dir).with_context(|| {
+                format!(
+                    "Failed to canonicalize excluded directory: {}",
+                    dir.display()
+                )
+            })
         })
         .collect::<Result<Vec<_>, _>>()?;
 
@@ -72,30 +286,83 @@ pub fn determine_trash_dir_for(item: &Path, config: &Config) -> Result<PathBuf>
             .map(Path::to_owned),
     );
 
+    // Add user-provided directories to always exclude, handled like the built-in ones
+    exclude.extend(config.always_exclude.iter().cloned());
+
+    Ok(exclude)
+}
+
+/// Compile the glob patterns of paths to never create a trash directory for
+pub fn compute_exclusion_patterns(config: &Config) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+
+    for pattern in &config.exclude_pattern {
+        builder.add(pattern.clone());
+    }
+
+    builder
+        .build()
+        .context("Failed to compile exclusion patterns")
+}
+
+/// Determine path to the trash directory for a given item and create it if required
+pub fn determine_trash_dir_for(item: &Path, config: &Config) -> Result<PathBuf> {
+    determine_trash_dir_with(item, absolute_item_path, config)
+}
+
+/// Determine the trash directory for an item that may not exist (yet)
+///
+/// The item's path is resolved from its nearest existing ancestor, see [`lenient_absolute_path`].
+pub fn determine_trash_dir_for_lenient(item: &Path, config: &Config) -> Result<PathBuf> {
+    determine_trash_dir_with(item, lenient_absolute_path, config)
+}
+
+fn determine_trash_dir_with(
+    item: &Path,
+    resolve: impl Fn(&Path) -> Result<PathBuf>,
+    config: &Config,
+) -> Result<PathBuf> {
+    debug!("Determining trasher directory for item: {}", item.display());
+
+    if let Some(trash_dir) = forced_trash_dir(config)? {
+        return Ok(trash_dir);
+    }
+
+    let home_dir = canonical_home_dir()?;
+
+    let exclude = compute_exclusions(config)?;
+
     // Don't canonicalize excluded item paths
     // NOTE: Only works if item path is absolute
     if exclude.iter().any(|dir| item.starts_with(dir)) {
-        return Ok(home_dir.join(TRASH_DIR_NAME));
+        return home_trash_dir(config);
     }
 
-    let item = fs::canonicalize(item)
+    let item = resolve(item)
         .with_context(|| format!("Failed to canonicalize item path: {}\n\nTip: you can exclude this directory using --exclude.", item.display()))?;
 
-    let mut mountpoints = mountpaths().context("Failed to list system mountpoints")?;
+    let exclude_patterns = compute_exclusion_patterns(config)?;
+
+    if item
+        .ancestors()
+        .any(|ancestor| exclude_patterns.is_match(ancestor))
+    {
+        return home_trash_dir(config);
+    }
 
-    // Add home directory for specialization
-    // e.g. if "/home" is a mounted directory, and we delete an item instead "/home/$USER",
-    // this line will allow the algorithm to pick the more specialized "/home/$USER" instead
-    mountpoints.push(home_dir.clone());
+    let mountpoints = canonical_mountpoints_with_home(
+        mountpaths().context("Failed to list system mountpoints")?,
+        &home_dir,
+    );
 
     let mut found = None::<PathBuf>;
 
-    for mountpoint in &mountpoints {
+    for mountpoint in mountpoints {
         if mountpoint.to_str() == Some("/") {
             continue;
         }
 
-        let Ok(mt) = fs::metadata(mountpoint) else {
+        let Ok(mt) = fs::metadata(&mountpoint) else {
             continue;
         };
 
@@ -113,14 +380,7 @@ pub fn determine_trash_dir_for(item: &Path, config: &Config) -> Result<PathBuf>
             }
         }
 
-        let canon_mountpoint = fs::canonicalize(mountpoint).with_context(|| {
-            format!(
-                "Failed to canonicalize mountpoint: {}",
-                mountpoint.display()
-            )
-        })?;
-
-        if !item.starts_with(&canon_mountpoint) {
+        if !item.starts_with(&mountpoint) || exclude_patterns.is_match(&mountpoint) {
             continue;
         }
 
@@ -129,351 +389,2365 @@ pub fn determine_trash_dir_for(item: &Path, config: &Config) -> Result<PathBuf>
             break;
         }
 
-        if found.is_none() || matches!(found, Some(ref prev) if canon_mountpoint.starts_with(prev))
-        {
-            found = Some(canon_mountpoint);
+        if found.is_none() || matches!(found, Some(ref prev) if mountpoint.starts_with(prev)) {
+            found = Some(mountpoint);
         }
     }
 
-    Ok(found.unwrap_or(home_dir).join(TRASH_DIR_NAME))
+    let mountpoint = found.unwrap_or_else(|| home_dir.clone());
+
+    // Bind mounts may make the selected mountpoint differ from the item's actual filesystem
+    debug!(
+        "Selected mountpoint: {} (device ID: {})",
+        mountpoint.display(),
+        get_dev(fs::metadata(&mountpoint))
+            .map_or_else(|_| "unknown".to_owned(), |dev| dev.to_string())
+    );
+
+    if mountpoint == home_dir {
+        return home_trash_dir(config);
+    }
+
+    Ok(mountpoint.join(TRASH_DIR_NAME))
 }
 
-/// List all trash directories
+/// Canonicalize mountpoints (keeping the ones that can't be as they are) and add the home directory to them
+///
+/// The home directory is added for specialization: e.g. if "/home" is a mounted directory, and we delete
+/// an item inside "/home/$USER", this allows the algorithm to pick the more specialized "/home/$USER" instead.
+///
+/// Mountpoints are canonicalized first, as the home directory (which is canonical) may be listed
+/// through a symbolic link (e.g. "/home" pointing to "/var/home").
+fn canonical_mountpoints_with_home(mountpoints: Vec<PathBuf>, home_dir: &Path) -> Vec<PathBuf> {
+    let mut mountpoints = mountpoints
+        .into_iter()
+        .map(|mountpoint| fs::canonicalize(&mountpoint).unwrap_or(mountpoint))
+        .collect::<Vec<_>>();
+
+    if !mountpoints.iter().any(|mountpoint| mountpoint == home_dir) {
+        mountpoints.push(home_dir.to_owned());
+    }
+
+    mountpoints
+}
+
+/// List all existing trash directories
 pub fn list_trash_dirs(config: &Config) -> Result<BTreeSet<PathBuf>> {
+    let mut trash_dirs = list_potential_trash_dirs(config)?;
+
+    trash_dirs.retain(|dir| {
+        if let Err(err) = ensure_trash_dir_is_dir(dir) {
+            warn!("{err}");
+        }
+
+        dir.is_dir()
+    });
+
+    Ok(trash_dirs)
+}
+
+/// Ensure the path of a trash directory isn't taken by something else (e.g. a regular file)
+pub fn ensure_trash_dir_is_dir(trash_dir: &Path) -> Result<()> {
+    let Ok(mt) = fs::metadata(trash_dir) else {
+        return Ok(());
+    };
+
+    if !mt.is_dir() {
+        bail!(
+            "Trash directory path '{}' exists but is not a directory\n\nTip: move or remove it so the trash directory can be created there.",
+            trash_dir.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// List the trash directories of all mountpoints, whether they exist or not
+pub fn list_potential_trash_dirs(config: &Config) -> Result<BTreeSet<PathBuf>> {
+    if let Some(trash_dir) = forced_trash_dir(config)? {
+        return Ok(BTreeSet::from([trash_dir]));
+    }
+
     let canon_root = fs::canonicalize("/").context("Failed to canonicalize the root directory")?;
 
-    let trash_dirs = mountpaths()
+    mountpaths()
         .context("Failed to list system mountpoints")?
         .iter()
         .chain([canon_root].iter())
         .map(|path| determine_trash_dir_for(path, config))
-        .collect::<Result<Vec<_>, _>>()?;
-
-    Ok(trash_dirs.into_iter().filter(|dir| dir.is_dir()).collect())
+        .collect()
 }
 
-/// List and parse all items in the trash
-pub fn list_trash_items(trash_dir: &Path) -> Result<Vec<TrashedItem>> {
-    if !trash_dir.exists() {
-        return Ok(vec![]);
-    }
+/// Check if a path points to a trash directory (by its name)
+pub fn is_trash_dir_path(path: &Path) -> bool {
+    path.file_name() == Some(OsStr::new(TRASH_DIR_NAME))
+}
 
-    let dir_entries = fs::read_dir(trash_dir)
-        .context("Failed to read trash directory")?
-        .collect::<Result<Vec<_>, _>>()?;
+/// Get the name of the partial transfer directory inside trash directories
+pub fn transfer_dir_name(config: &Config) -> &str {
+    config
+        .transfer_dir_name
+        .as_deref()
+        .unwrap_or(TRASH_TRANSFER_DIRNAME)
+}
 
-    let items = dir_entries
-        .into_iter()
-        .filter_map(|item| {
-            match item.file_name().into_string() {
-                Err(_) => error!(
-                    "WARN: Trash item '{}' does not have a valid UTF-8 filename!",
-                    item.path().display()
-                ),
+/// Validate a custom name for the partial transfer directory
+///
+/// The directory must be located directly inside the trash directory so items can be
+/// atomically renamed from it, and its name must not be mistaken for a trash item's one.
+pub fn parse_transfer_dir_name(name: &str) -> Result<String, String> {
+    if name.is_empty() || name == "." || name == ".." {
+        return Err("Transfer directory name must be a valid directory name".to_owned());
+    }
 
-                Ok(filename) => {
-                    if filename == TRASH_TRANSFER_DIRNAME {
-                        return None;
-                    }
+    if name.contains(['/', '\\']) {
+        return Err("Transfer directory name cannot contain path separators".to_owned());
+    }
 
-                    match TrashItemInfos::decode(&filename) {
-                        Err(err) => {
-                            error!(
-                                "WARN: Trash item '{}' does not have a valid trash filename!",
-                                item.path().display()
-                            );
-
-                            super::debug!("Invalid trash item filename: {:?}", err);
-                        }
-
-                        Ok(item) => {
-                            return Some(TrashedItem {
-                                data: item,
-                                trash_dir: trash_dir.to_path_buf(),
-                            })
-                        }
-                    }
-                }
-            }
+    if name == TRASH_METADATA_DIRNAME {
+        return Err("Transfer directory name is reserved for the metadata directory".to_owned());
+    }
 
-            None
-        })
-        .collect();
+    if TrashItemInfos::decode(OsStr::new(name)).is_ok() {
+        return Err("Transfer directory name cannot be a valid trash item name".to_owned());
+    }
 
-    Ok(items)
+    Ok(name.to_owned())
 }
 
-/// List all trash items
-pub fn list_all_trash_items(config: &Config) -> Result<Vec<TrashedItem>> {
-    let all_trash_items = list_trash_dirs(config)?
-        .into_iter()
-        .map(|trash_dir| list_trash_items(&trash_dir))
-        .collect::<Result<Vec<_>, _>>()?;
+/// Validate a custom name to store a trash item under
+pub fn parse_item_name(name: &str) -> Result<String, String> {
+    if name.is_empty() || name == "." || name == ".." {
+        return Err("Item name must be a valid file name".to_owned());
+    }
 
-    let mut items = all_trash_items.into_iter().flatten().collect::<Vec<_>>();
-    items.sort_by_key(|item| item.data.datetime);
+    if name.contains(['/', '\\']) {
+        return Err("Item name cannot contain path separators".to_owned());
+    }
 
-    Ok(items)
+    Ok(name.to_owned())
 }
 
-/// Find a specific item in the trash (panic if not found)
-pub fn expect_trash_item(
-    filename: &str,
-    id: Option<&str>,
-    config: &Config,
-) -> Result<FoundTrashItems> {
-    let mut candidates = list_all_trash_items(config)?
-        .into_iter()
-        .filter(|trashed| trashed.data.filename == filename)
-        .collect::<Vec<_>>();
+/// Get the names of the transfer directories leftovers may be located in
+///
+/// This includes the default transfer directory as well as the custom one, if any
+fn transfer_dir_names(config: &Config) -> Vec<&str> {
+    let mut names = vec![TRASH_TRANSFER_DIRNAME, transfer_dir_name(config)];
+    names.dedup();
+    names
+}
 
-    if candidates.is_empty() {
-        bail!("Specified item was not found in the trash.");
-    } else if candidates.len() > 1 {
-        match id {
-            None => Ok(FoundTrashItems::Multi(candidates)),
-            Some(id) => Ok(FoundTrashItems::Single(
-                candidates
-                    .into_iter()
-                    .find(|c| c.data.compute_id() == id)
-                    .context("There is no trash item with the provided ID")?,
-            )),
-        }
-    } else {
-        Ok(FoundTrashItems::Single(candidates.remove(0)))
-    }
+/// Check if an entry of a trash directory is one of Trasher's internal directories
+fn is_internal_trash_entry(filename: &OsStr, config: &Config) -> bool {
+    filename == TRASH_TRANSFER_DIRNAME
+        || filename == TRASH_METADATA_DIRNAME
+        || filename == TRASH_RECOVERY_FILENAME
+        || filename == TRASH_QUARANTINE_DIRNAME
+        || filename == transfer_dir_name(config)
 }
 
-/// Find a specific item in the trash, fail if none is found or if multiple candidates are found
-pub fn expect_single_trash_item(
-    filename: &str,
-    id: Option<&str>,
-    config: &Config,
-) -> Result<TrashedItem> {
-    match expect_trash_item(filename, id, config)? {
-        FoundTrashItems::Single(item) => Ok(item),
-        FoundTrashItems::Multi(candidates) => bail!(
-            "Multiple items with this filename were found in the trash:\n\n{}",
-            table_for_items(&candidates)
-        ),
-    }
+/// Write the file explaining how to recover items in a trash directory
+pub fn write_recovery_note(trash_dir: &Path) -> Result<()> {
+    let path = trash_dir.join(TRASH_RECOVERY_FILENAME);
+
+    fs::write(&path, TRASH_RECOVERY_NOTE)
+        .with_context(|| format!("Failed to write recovery file: {}", path.display()))
 }
 
-/// Convert a size in bytes to a human-readable size
-pub fn human_readable_size(bytes: u64) -> String {
-    let names = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+/// Check if a trash directory is empty, only containing Trasher's internal directories with nothing inside
+pub fn is_empty_trash_dir(trash_dir: &Path, config: &Config) -> Result<bool> {
+    for entry in fs::read_dir(trash_dir).context("Failed to read trash directory")? {
+        let entry = entry.context("Failed to read trash directory entry")?;
 
-    if bytes < 1024 {
-        return format!("{} B", bytes);
-    }
+        if entry.file_name() == TRASH_RECOVERY_FILENAME {
+            continue;
+        }
 
-    let mut compare = 1024;
+        if !is_internal_trash_entry(&entry.file_name(), config) {
+            return Ok(false);
+        }
 
-    for name in names.iter() {
-        compare *= 1024;
+        let is_empty_dir = entry.file_type().is_ok_and(|file_type| file_type.is_dir())
+            && fs::read_dir(entry.path())
+                .with_context(|| format!("Failed to read directory: {}", entry.path().display()))?
+                .next()
+                .is_none();
 
-        if bytes <= compare {
-            return format!("{:.2} {}", bytes as f64 * 1024f64 / compare as f64, name);
+        if !is_empty_dir {
+            return Ok(false);
         }
     }
 
-    format!(
-        "{:.2} {}",
-        bytes as f64 / compare as f64,
-        names.last().unwrap()
-    )
+    Ok(true)
 }
 
-/// Trash item with the trash directory is contained into, generated by the [`list_trash_items`] function
-#[derive(Debug, Clone)]
-pub struct TrashedItem {
-    pub data: TrashItemInfos,
-    pub trash_dir: PathBuf,
+/// List and parse all items in the trash
+pub fn list_trash_items(trash_dir: &Path, config: &Config) -> Result<Vec<TrashedItem>> {
+    iter_trash_items(trash_dir, config)?.collect()
 }
 
-impl TrashedItem {
-    /// Get the trash path for an item that's going to be transferred to it
-    pub fn transfer_trash_item_path(&self) -> PathBuf {
-        self.trash_dir
-            .join(TRASH_TRANSFER_DIRNAME)
-            .join(self.data.trash_filename())
-    }
-
-    pub fn complete_trash_item_path(&self) -> PathBuf {
-        self.trash_dir.join(self.data.trash_filename())
+/// Parse the items in the trash one by one, as they are read from the trash directory
+///
+/// Unlike [`list_trash_items`], this doesn't keep all items in memory, which matters for very large trashes.
+///
+/// Items whose transfer was interrupted before they were moved out of the transfer directory
+/// are listed as pending, so they can still be recovered or dropped.
+pub fn iter_trash_items<'a>(
+    trash_dir: &'a Path,
+    config: &'a Config,
+) -> Result<Box<dyn Iterator<Item = Result<TrashedItem>> + 'a>> {
+    if !trash_dir.exists() {
+        return Ok(Box::new(std::iter::empty()));
     }
-}
 
-/// Trash items found with the [`expect_trash_item`] function
-pub enum FoundTrashItems {
-    Single(TrashedItem),
-    Multi(Vec<TrashedItem>),
-}
+    ensure_trash_dir_is_dir(trash_dir)?;
 
-// Check if a path is dangerous to delete
-pub fn is_dangerous_path(path: &Path) -> bool {
-    let mut components = path.components();
+    let dir_entries = fs::read_dir(trash_dir).context("Failed to read trash directory")?;
 
-    match (
-        components.next(),
-        components.next(),
-        components.next(),
-        components.next(),
-    ) {
-        // The root directory (/)
-        (Some(Component::RootDir), None, None, None) => true,
+    let items = dir_entries.filter_map(move |item| {
+        let item = match item {
+            Ok(item) => item,
+            Err(err) => return Some(Err(err.into())),
+        };
 
-        // Root directories (/home, /bin, etc.)
-        (Some(Component::RootDir), Some(_), None, None) => true,
+        let filename = item.file_name();
 
-        // Home directories (/home/username, etc.)
-        (Some(Component::RootDir), Some(Component::Normal(dir)), Some(_), None) => {
-            dir == OsStr::new("home")
+        if is_internal_trash_entry(&filename, config) {
+            return None;
         }
 
-        // Non-dangerous paths
-        _ => false,
-    }
-}
+        match TrashItemInfos::decode(&filename) {
+            Err(err) => {
+                error!(
+                    "WARN: Trash item '{}' does not have a valid trash filename!",
+                    item.path().display()
+                );
 
-/// Move items around with a progressbar
-pub fn move_item_pbr(path: &Path, target: &Path) -> Result<()> {
-    let pbr = Rc::new(RefCell::new(None));
+                super::debug!("Invalid trash item filename: {:?}", err);
 
-    let update_pbr = |copied, total, item_name: &str| {
-        let mut pbr = pbr.borrow_mut();
-        let pbr = pbr.get_or_insert_with(|| {
-            let pbr = ProgressBar::new(total);
-            pbr.set_style(ProgressStyle::default_bar()
-            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
-            .expect("Invalid progress bar template")
-            .progress_chars("#>-"));
-            pbr
+                None
+            }
+
+            Ok(data) => Some(Ok(load_trash_item(data, trash_dir, None))),
+        }
+    });
+
+    let pending_items = transfer_dir_names(config)
+        .into_iter()
+        .map(move |transfer_dir_name| trash_dir.join(transfer_dir_name))
+        .filter(|transfer_dir| transfer_dir.is_dir())
+        .flat_map(move |transfer_dir| {
+            let entries = match fs::read_dir(&transfer_dir) {
+                Ok(entries) => entries,
+                Err(err) => {
+                    return vec![Err(
+                        anyhow!(err).context("Failed to read transfer directory")
+                    )]
+                }
+            };
+
+            entries
+                .filter_map(|entry| {
+                    let entry = match entry {
+                        Ok(entry) => entry,
+                        Err(err) => return Some(Err(err.into())),
+                    };
+
+                    // Entries without a valid name are reported by the 'doctor' command instead
+                    let data = TrashItemInfos::decode(&entry.file_name()).ok()?;
+
+                    Some(Ok(load_trash_item(
+                        data,
+                        trash_dir,
+                        Some(transfer_dir.clone()),
+                    )))
+                })
+                .collect()
         });
 
-        pbr.set_position(copied);
-        pbr.set_message(item_name.to_string());
-    };
+    Ok(Box::new(items.chain(pending_items)))
+}
 
-    if path.metadata()?.is_file() {
-        let file_name = path.file_name().unwrap().to_string_lossy();
+/// Build a trash item from its decoded filename, reading its metadata
+fn load_trash_item(
+    data: TrashItemInfos,
+    trash_dir: &Path,
+    pending_in: Option<PathBuf>,
+) -> TrashedItem {
+    let mut item = TrashedItem {
+        data,
+        trash_dir: trash_dir.to_path_buf(),
+        pending_in,
+    };
 
-        fs_extra::file::move_file_with_progress(
-            path,
-            target,
-            &fs_extra::file::CopyOptions::new(),
-            |tp| {
-                update_pbr(tp.copied_bytes, tp.total_bytes, &file_name);
-            },
-        )?;
-    } else {
-        let mut config = fs_extra::dir::CopyOptions::new();
-        config.copy_inside = true;
-        fs_extra::dir::move_dir_with_progress(path, target, &config, |tp| {
-            update_pbr(tp.copied_bytes, tp.total_bytes, &tp.file_name);
-            TransitProcessResult::ContinueOrAbort
-        })?;
+    if item.data.has_lossy_filename() {
+        debug!(
+            "Trash item '{}' does not have a valid UTF-8 filename",
+            item.complete_trash_item_path().display()
+        );
     }
 
-    let mut pbr = pbr.borrow_mut();
-    let pbr = pbr.as_mut();
-
-    if let Some(pbr) = pbr {
-        pbr.finish_with_message("Moving complete.")
+    match item.read_metadata() {
+        Ok(Some(metadata)) => item.data.metadata = metadata,
+        Ok(None) => {}
+        Err(err) => error!(
+            "WARN: Failed to read metadata of trash item '{}': {err:?}",
+            item.complete_trash_item_path().display()
+        ),
     }
 
-    Ok(())
+    item
+}
+
+/// List all trash items
+pub fn list_all_trash_items(config: &Config) -> Result<Vec<TrashedItem>> {
+    list_trash_items_of(&list_trash_dirs(config)?, config)
+}
+
+/// List the items of the provided trash directories, sorted by deletion date
+pub fn list_trash_items_of<'a>(
+    trash_dirs: impl IntoIterator<Item = &'a PathBuf>,
+    config: &Config,
+) -> Result<Vec<TrashedItem>> {
+    // Trash directories are listed in parallel so a slow one (e.g. on a network storage)
+    // doesn't delay the other ones
+    let all_trash_items = std::thread::scope(|scope| {
+        let handles = trash_dirs
+            .into_iter()
+            .map(|trash_dir| scope.spawn(|| list_trash_items(trash_dir, config)))
+            .collect::<Vec<_>>();
+
+        // Results are joined in the trash directories' order, to keep the output deterministic
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .expect("Trash directory listing thread panicked")
+            })
+            .collect::<Result<Vec<_>, _>>()
+    })?;
+
+    let mut items = all_trash_items.into_iter().flatten().collect::<Vec<_>>();
+    items.sort_by_key(|item| item.data.datetime);
+
+    Ok(items)
+}
+
+/// Validate trash directories provided to restrict an operation to, which must exist
+pub fn resolve_trash_dirs_scope(paths: &[PathBuf], config: &Config) -> Result<Vec<PathBuf>> {
+    if paths.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let trash_dirs = list_trash_dirs(config)?;
+
+    paths
+        .iter()
+        .map(|path| {
+            let canon_path = fs::canonicalize(path).ok();
+
+            match canon_path.filter(|path| trash_dirs.contains(path)) {
+                Some(trash_dir) => Ok(trash_dir),
+                None => bail!(
+                    "Path '{}' is not an existing trash directory\n\nTip: use 'trasher trash-path --all' to list them.",
+                    path.display()
+                ),
+            }
+        })
+        .collect()
+}
+
+/// Find inconsistencies in a trash directory
+pub fn find_trash_issues(trash_dir: &Path, config: &Config) -> Result<Vec<TrashIssue>> {
+    let mut issues = vec![];
+
+    for transfer_dir_name in transfer_dir_names(config) {
+        let transfer_dir = trash_dir.join(transfer_dir_name);
+
+        if !transfer_dir.is_dir() {
+            continue;
+        }
+
+        for entry in fs::read_dir(&transfer_dir).context("Failed to read transfer directory")? {
+            let entry = entry.context("Failed to read transfer directory entry")?;
+
+            let mt = entry.metadata().with_context(|| {
+                format!(
+                    "Failed to get metadata for item: {}",
+                    entry.path().display()
+                )
+            })?;
+
+            if mt.is_file() && mt.len() == 0 {
+                issues.push(TrashIssue::EmptyPartialTransfer(entry.path()));
+                continue;
+            }
+
+            let item = TrashItemInfos::decode(&entry.file_name())
+                .ok()
+                .map(|data| load_trash_item(data, trash_dir, Some(transfer_dir.clone())));
+
+            issues.push(match item {
+                Some(item) if item.is_known_complete() => {
+                    TrashIssue::CompletePartialTransfer(entry.path())
+                }
+                item => TrashIssue::PartialTransfer(
+                    entry.path(),
+                    item.and_then(|item| item.data.metadata.original_path),
+                ),
+            });
+        }
+    }
+
+    for entry in fs::read_dir(trash_dir).context("Failed to read trash directory")? {
+        let entry = entry.context("Failed to read trash directory entry")?;
+        let filename = entry.file_name();
+
+        if !is_internal_trash_entry(&filename, config) && TrashItemInfos::decode(&filename).is_err()
+        {
+            issues.push(TrashIssue::UndecodableItem(entry.path()));
+        }
+    }
+
+    let metadata_dir = trash_dir.join(TRASH_METADATA_DIRNAME);
+
+    if metadata_dir.is_dir() {
+        for entry in fs::read_dir(&metadata_dir).context("Failed to read metadata directory")? {
+            let entry = entry.context("Failed to read metadata directory entry")?;
+            let path = entry.path();
+
+            // Metadata is written before the item is transferred, so it may still be in a transfer directory
+            let has_item = path.file_stem().is_some_and(|item_filename| {
+                trash_dir.join(item_filename).exists()
+                    || transfer_dir_names(config).iter().any(|transfer_dir_name| {
+                        trash_dir
+                            .join(transfer_dir_name)
+                            .join(item_filename)
+                            .exists()
+                    })
+            });
+
+            if !has_item {
+                issues.push(TrashIssue::OrphanMetadata(path));
+            }
+        }
+    }
+
+    Ok(issues)
+}
+
+/// Repair an inconsistency in a trash directory, returning a description of what was done
+pub fn fix_trash_issue(trash_dir: &Path, issue: &TrashIssue) -> Result<String> {
+    match issue {
+        TrashIssue::EmptyPartialTransfer(path) => {
+            fs::remove_file(path).with_context(|| {
+                format!("Failed to remove partial transfer: {}", path.display())
+            })?;
+
+            Ok("removed empty partial transfer".to_owned())
+        }
+
+        TrashIssue::CompletePartialTransfer(path) => {
+            // Items in the transfer directory are already named after their final name
+            let target = trash_dir.join(path.file_name().unwrap());
+
+            if target.exists() {
+                bail!(
+                    "Cannot complete partial transfer as an item already exists at path: {}",
+                    target.display()
+                );
+            }
+
+            fs::rename(path, &target).with_context(|| {
+                format!("Failed to complete partial transfer: {}", path.display())
+            })?;
+
+            Ok(format!(
+                "moved to the trash as '{}'",
+                target.file_name().unwrap().to_string_lossy()
+            ))
+        }
+
+        // The copy may be incomplete, so it's kept aside instead of being made restorable
+        TrashIssue::PartialTransfer(path, original_path) => {
+            let filename = path.file_name().unwrap();
+            let quarantine_dir = trash_dir.join(TRASH_QUARANTINE_DIRNAME);
+
+            if !quarantine_dir.exists() {
+                fs::create_dir(&quarantine_dir).with_context(|| {
+                    format!(
+                        "Failed to create quarantine directory at path '{}'",
+                        quarantine_dir.display()
+                    )
+                })?;
+            }
+
+            let target = quarantine_dir.join(filename);
+
+            if fs::symlink_metadata(&target).is_ok() {
+                bail!(
+                    "Cannot quarantine partial transfer as an item already exists at path: {}",
+                    target.display()
+                );
+            }
+
+            fs::rename(path, &target).with_context(|| {
+                format!("Failed to quarantine partial transfer: {}", path.display())
+            })?;
+
+            let mut metadata_filename = filename.to_owned();
+            metadata_filename.push(".json");
+
+            let metadata_path = trash_dir
+                .join(TRASH_METADATA_DIRNAME)
+                .join(metadata_filename);
+
+            if metadata_path.exists() {
+                fs::remove_file(&metadata_path).with_context(|| {
+                    format!(
+                        "Failed to remove metadata file: {}",
+                        metadata_path.display()
+                    )
+                })?;
+            }
+
+            Ok(match original_path {
+                Some(original_path) => format!(
+                    "moved to '{}' as it may be an incomplete copy (the original item is still at '{}')",
+                    target.display(),
+                    original_path.display()
+                ),
+                None => format!(
+                    "moved to '{}' as it may be an incomplete copy",
+                    target.display()
+                ),
+            })
+        }
+
+        TrashIssue::OrphanMetadata(path) => {
+            fs::remove_file(path)
+                .with_context(|| format!("Failed to remove metadata file: {}", path.display()))?;
+
+            Ok("removed orphan metadata file".to_owned())
+        }
+
+        TrashIssue::UndecodableItem(path) => {
+            let data = TrashItemInfos::new_now(path.file_name().unwrap().to_owned());
+            let target = trash_dir.join(data.trash_filename());
+
+            fs::rename(path, &target)
+                .with_context(|| format!("Failed to rename item: {}", path.display()))?;
+
+            Ok(format!(
+                "renamed to '{}'",
+                target.file_name().unwrap().to_string_lossy()
+            ))
+        }
+    }
+}
+
+/// Find a specific item in the trash (fail if not found)
+///
+/// When no filename is provided, the item is looked up by its ID alone.
+/// If trash directories are provided, only their items are considered.
+pub fn expect_trash_item(
+    filename: Option<&str>,
+    id: Option<&str>,
+    trash_dirs: &[PathBuf],
+    config: &Config,
+) -> Result<FoundTrashItems> {
+    let items = if trash_dirs.is_empty() {
+        list_all_trash_items(config)?
+    } else {
+        list_trash_items_of(trash_dirs, config)?
+    };
+
+    let Some(filename) = filename else {
+        let id = id.context("Either a filename or an ID must be provided")?;
+
+        let mut candidates = items
+            .into_iter()
+            .filter(|trashed| trashed.data.compute_id() == id)
+            .collect::<Vec<_>>();
+
+        return match candidates.len() {
+            0 => bail!(TrasherError::NoItemWithId),
+            1 => Ok(FoundTrashItems::Single(Box::new(candidates.remove(0)))),
+            _ => Ok(FoundTrashItems::Multi(candidates)),
+        };
+    };
+
+    let mut candidates = items
+        .iter()
+        .filter(|trashed| trashed.data.filename_lossy() == filename)
+        .cloned()
+        .collect::<Vec<_>>();
+
+    // Support the combined '<filename>@<id>' syntax, unless an item is literally named this way
+    if candidates.is_empty() && id.is_none() {
+        if let Some((filename, id)) = filename.rsplit_once(ITEM_ID_DELIMITER) {
+            candidates = items
+                .into_iter()
+                .filter(|trashed| {
+                    trashed.data.filename_lossy() == filename && trashed.data.compute_id() == id
+                })
+                .collect();
+        }
+    }
+
+    if candidates.is_empty() {
+        bail!(TrasherError::ItemNotFound);
+    } else if candidates.len() > 1 {
+        match id {
+            None => Ok(FoundTrashItems::Multi(candidates)),
+            Some(id) => Ok(FoundTrashItems::Single(Box::new(
+                candidates
+                    .into_iter()
+                    .find(|c| c.data.compute_id() == id)
+                    .ok_or(TrasherError::NoItemWithId)?,
+            ))),
+        }
+    } else {
+        Ok(FoundTrashItems::Single(Box::new(candidates.remove(0))))
+    }
+}
+
+/// Find a specific item in the trash, fail if none is found or if multiple candidates are found
+///
+/// If `json_candidates` is set, candidates are printed as JSON instead of letting the user pick one.
+pub fn expect_single_trash_item(
+    filename: Option<&str>,
+    id: Option<&str>,
+    trash_dirs: &[PathBuf],
+    json_candidates: bool,
+    config: &Config,
+) -> Result<TrashedItem> {
+    match expect_trash_item(filename, id, trash_dirs, config)? {
+        FoundTrashItems::Single(item) => Ok(*item),
+
+        FoundTrashItems::Multi(candidates) if json_candidates => {
+            println!("{}", json_for_items(&candidates)?);
+            bail!(TrasherError::AmbiguousMatchPrinted)
+        }
+
+        // Let the user pick an item when running in an interactive terminal
+        FoundTrashItems::Multi(candidates)
+            if io::stdin().is_terminal() && io::stdout().is_terminal() =>
+        {
+            run_fuzzy_finder(
+                candidates
+                    .into_iter()
+                    .map(|item| FuzzyFinderItem {
+                        display: format!(
+                            "[{}] {} ({}) in {}",
+                            format_date(item.data.datetime),
+                            item.data.filename_lossy(),
+                            item.data.compute_id(),
+                            item.trash_dir.display()
+                        ),
+                        date: item.data.datetime,
+                        value: item,
+                    })
+                    .collect(),
+                config.ignore_case,
+                None,
+            )
+        }
+
+        FoundTrashItems::Multi(candidates) => bail!(TrasherError::AmbiguousMatch(candidates)),
+    }
+}
+
+/// Units used to display sizes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SizeFormat {
+    /// 1024-based units (KiB, MiB, ...)
+    #[default]
+    Iec,
+
+    /// 1000-based units (kB, MB, ...)
+    Si,
+}
+
+impl SizeFormat {
+    fn base_and_units(self) -> (u64, [&'static str; 6]) {
+        match self {
+            Self::Iec => (1024, ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"]),
+            Self::Si => (1000, ["kB", "MB", "GB", "TB", "PB", "EB"]),
+        }
+    }
+}
+
+/// Convert a size in bytes to a human-readable size
+pub fn human_readable_size(bytes: u64, format: SizeFormat) -> String {
+    let (base, units) = format.base_and_units();
+
+    if bytes < base {
+        return format!("{} B", bytes);
+    }
+
+    let base = base as f64;
+    let mut value = bytes as f64 / base;
+    let mut unit = units[0];
+
+    for next_unit in &units[1..] {
+        // Compare the rounded value so e.g. 1048575 bytes isn't displayed as "1024.00 KiB"
+        if (value * 100.0).round() / 100.0 < base {
+            break;
+        }
+
+        value /= base;
+        unit = next_unit;
+    }
+
+    format!("{value:.2} {unit}")
+}
+
+/// Units accepted when parsing sizes, with their size in bytes
+static SIZE_UNITS: &[(&str, u64)] = &[
+    ("B", 1),
+    ("K", 1 << 10),
+    ("KiB", 1 << 10),
+    ("M", 1 << 20),
+    ("MiB", 1 << 20),
+    ("G", 1 << 30),
+    ("GiB", 1 << 30),
+    ("T", 1 << 40),
+    ("TiB", 1 << 40),
+    ("kB", 1_000),
+    ("KB", 1_000),
+    ("MB", 1_000_000),
+    ("GB", 1_000_000_000),
+    ("TB", 1_000_000_000_000),
+];
+
+/// Parse a human-readable size (e.g. `512`, `100MiB`, `1.5G`, `10MB`) into a number of bytes
+pub fn parse_size(input: &str) -> Result<u64, String> {
+    let input = input.trim();
+
+    let unit_pos = input
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(input.len());
+
+    let (number, unit) = input.split_at(unit_pos);
+
+    let number = number
+        .parse::<f64>()
+        .map_err(|_| format!("Invalid number in size '{input}'"))?;
+
+    let multiplier = match unit.trim() {
+        "" => 1,
+        unit => SIZE_UNITS
+            .iter()
+            .find(|(name, _)| *name == unit)
+            .map(|(_, multiplier)| *multiplier)
+            .ok_or_else(|| format!("Unknown unit '{unit}' in size '{input}'"))?,
+    };
+
+    Ok((number * multiplier as f64).round() as u64)
+}
+
+/// Compute the size of an item, recursively if it's a directory (symbolic links are not followed)
+pub fn item_size(path: &Path) -> Result<u64> {
+    let mut size = 0;
+    let mut skipped_loops = 0;
+
+    // Symbolic links are never followed, so their own size is counted instead of their target's
+    for entry in WalkDir::new(path).follow_links(false) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) if err.loop_ancestor().is_some() => {
+                skipped_loops += 1;
+                continue;
+            }
+            Err(err) => return Err(err).context("Failed to read directory entry"),
+        };
+
+        let mt = entry.metadata().with_context(|| {
+            format!(
+                "Failed to get metadata for item: {}",
+                entry.path().display()
+            )
+        })?;
+
+        if mt.is_file() {
+            size += mt.len();
+        }
+    }
+
+    if skipped_loops > 0 {
+        debug!(
+            "Skipped {} filesystem loop(s) while computing size of item: {}",
+            skipped_loops,
+            path.display()
+        );
+    }
+
+    Ok(size)
+}
+
+/// Trash item with the trash directory is contained into, generated by the [`list_trash_items`] function
+#[derive(Debug, Clone)]
+pub struct TrashedItem {
+    pub data: TrashItemInfos,
+    pub trash_dir: PathBuf,
+
+    /// Transfer directory the item was left in, if its transfer to the trash was interrupted
+    pub pending_in: Option<PathBuf>,
+}
+
+impl TrashedItem {
+    /// Get the trash path for an item that's going to be transferred to it
+    pub fn transfer_trash_item_path(&self, config: &Config) -> PathBuf {
+        self.trash_dir
+            .join(transfer_dir_name(config))
+            .join(self.data.trash_filename())
+    }
+
+    pub fn complete_trash_item_path(&self) -> PathBuf {
+        self.pending_in
+            .as_ref()
+            .unwrap_or(&self.trash_dir)
+            .join(self.data.trash_filename())
+    }
+
+    /// Check if the item's transfer to the trash was interrupted (see [`Self::pending_in`])
+    pub fn is_pending(&self) -> bool {
+        self.pending_in.is_some()
+    }
+
+    /// Check if the item is known to be a complete copy of the original one
+    ///
+    /// Items are only removed from their original location once they're entirely copied,
+    /// so an item left in a transfer directory is complete if its original path is known
+    /// and doesn't exist anymore. Otherwise, its transfer may have been interrupted.
+    pub fn is_known_complete(&self) -> bool {
+        if !self.is_pending() {
+            return true;
+        }
+
+        self.data
+            .metadata
+            .original_path
+            .as_ref()
+            .is_some_and(|path| fs::symlink_metadata(path).is_err())
+    }
+
+    /// Get the path of the file containing the item's metadata
+    pub fn metadata_path(&self) -> PathBuf {
+        let mut filename = self.data.trash_filename();
+        filename.push(".json");
+
+        self.trash_dir.join(TRASH_METADATA_DIRNAME).join(filename)
+    }
+
+    /// Read the item's metadata, if any
+    pub fn read_metadata(&self) -> Result<Option<TrashItemMetadata>> {
+        let path = self.metadata_path();
+
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(&path).context("Failed to read metadata file")?;
+
+        let (version, metadata) =
+            TrashItemMetadata::decode(&content).context("Failed to parse metadata file")?;
+
+        if version > METADATA_VERSION {
+            debug!(
+                "Metadata file '{}' was written with a newer format (version {}), unknown informations will be ignored",
+                path.display(),
+                version
+            );
+        }
+
+        Ok(Some(metadata))
+    }
+
+    /// Write the item's metadata to the trash, flushing it to the disk if `sync` is set
+    pub fn write_metadata(&self, sync: bool) -> Result<()> {
+        let metadata_dir = self.trash_dir.join(TRASH_METADATA_DIRNAME);
+
+        if !metadata_dir.exists() {
+            fs::create_dir(&metadata_dir).with_context(|| {
+                format!(
+                    "Failed to create trash's metadata directory at path '{}'",
+                    metadata_dir.display()
+                )
+            })?;
+        }
+
+        let content = self
+            .data
+            .metadata
+            .encode()
+            .context("Failed to serialize item's metadata")?;
+
+        let path = self.metadata_path();
+
+        fs::write(&path, content).context("Failed to write item's metadata")?;
+
+        if sync {
+            sync_path(&path)?;
+            sync_path(&metadata_dir)?;
+        }
+
+        Ok(())
+    }
+
+    /// Remove the item's metadata from the trash, if any
+    pub fn discard_metadata(&self) -> Result<()> {
+        let path = self.metadata_path();
+
+        if path.exists() {
+            fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove metadata file: {}", path.display()))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Inconsistency in a trash directory, detected by the [`find_trash_issues`] function
+pub enum TrashIssue {
+    EmptyPartialTransfer(PathBuf),
+    /// Partial transfer known to be complete, which only needs to be moved to the trash
+    CompletePartialTransfer(PathBuf),
+    /// Partial transfer which may be incomplete, with the original item's path if it's known
+    PartialTransfer(PathBuf, Option<PathBuf>),
+    UndecodableItem(PathBuf),
+    OrphanMetadata(PathBuf),
+}
+
+impl std::fmt::Display for TrashIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EmptyPartialTransfer(path) => {
+                write!(f, "Empty leftover partial transfer: {}", path.display())
+            }
+            Self::CompletePartialTransfer(path) => {
+                write!(f, "Leftover complete transfer: {}", path.display())
+            }
+            Self::PartialTransfer(path, _) => {
+                write!(
+                    f,
+                    "Leftover partial transfer (may be incomplete): {}",
+                    path.display()
+                )
+            }
+            Self::UndecodableItem(path) => {
+                write!(f, "Item with an invalid trash filename: {}", path.display())
+            }
+            Self::OrphanMetadata(path) => {
+                write!(f, "Metadata file without a trash item: {}", path.display())
+            }
+        }
+    }
+}
+
+/// Trash items found with the [`expect_trash_item`] function
+pub enum FoundTrashItems {
+    Single(Box<TrashedItem>),
+    Multi(Vec<TrashedItem>),
+}
+
+// Check if a path is dangerous to delete
+pub fn is_dangerous_path(path: &Path) -> bool {
+    let mut components = path.components();
+
+    match (
+        components.next(),
+        components.next(),
+        components.next(),
+        components.next(),
+    ) {
+        // The root directory (/)
+        (Some(Component::RootDir), None, None, None) => true,
+
+        // Root directories (/home, /bin, etc.)
+        (Some(Component::RootDir), Some(_), None, None) => true,
+
+        // Home directories (/home/username, etc.)
+        (Some(Component::RootDir), Some(Component::Normal(dir)), Some(_), None) => {
+            dir == OsStr::new("home")
+        }
+
+        // Non-dangerous paths
+        _ => false,
+    }
+}
+
+/// Format of the archives items can be exported to
+#[derive(Clone, Copy)]
+pub enum ArchiveFormat {
+    Tar,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    /// Determine the format of an archive from its extension
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let name = path
+            .file_name()
+            .context("Archive path has no file name")?
+            .to_string_lossy()
+            .to_lowercase();
+
+        if name.ends_with(".tar") {
+            Ok(Self::Tar)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Ok(Self::TarGz)
+        } else if name.ends_with(".zip") {
+            bail!("ZIP archives are not supported, supported extensions are .tar, .tar.gz and .tgz")
+        } else if name.ends_with(".zst") || name.ends_with(".tzst") {
+            bail!("Zstandard archives are not supported, supported extensions are .tar, .tar.gz and .tgz")
+        } else {
+            bail!(
+                "Unsupported archive format for '{}', supported extensions are .tar, .tar.gz and .tgz",
+                path.display()
+            )
+        }
+    }
+}
+
+/// Write an item into a new archive, under the provided name
+///
+/// Symbolic links are stored as such instead of being followed.
+pub fn write_archive(
+    item: &Path,
+    name: &Path,
+    archive: &Path,
+    format: ArchiveFormat,
+) -> Result<()> {
+    let file = fs::File::create(archive)
+        .with_context(|| format!("Failed to create archive: {}", archive.display()))?;
+
+    let writer = io::BufWriter::new(file);
+
+    // Streams are finished explicitly, as errors would be ignored when dropping them
+    let writer = match format {
+        ArchiveFormat::Tar => write_tar(writer, item, name)?,
+        ArchiveFormat::TarGz => write_tar(
+            flate2::write::GzEncoder::new(writer, flate2::Compression::default()),
+            item,
+            name,
+        )?
+        .finish()
+        .context("Failed to finish writing the compressed archive")?,
+    };
+
+    writer
+        .into_inner()
+        .map_err(|err| err.into_error())
+        .and_then(|file| file.sync_all())
+        .context("Failed to finish writing the archive")
+}
+
+fn write_tar<W: io::Write>(writer: W, item: &Path, name: &Path) -> Result<W> {
+    let mut builder = tar::Builder::new(writer);
+    builder.follow_symlinks(false);
+
+    if fs::symlink_metadata(item).is_ok_and(|mt| mt.is_dir()) {
+        builder.append_dir_all(name, item)
+    } else {
+        builder.append_path_with_name(item, name)
+    }
+    .with_context(|| format!("Failed to add item to archive: {}", item.display()))?;
+
+    builder
+        .into_inner()
+        .context("Failed to finish writing the archive")
+}
+
+/// Copy items around with a progressbar, retrying up to the provided number of times on transient errors
+///
+/// The partial copy is removed between attempts, and the delay between them is doubled each time.
+pub fn copy_item_with_retries(path: &Path, target: &Path, retries: u32) -> Result<u64> {
+    let mut delay = Duration::from_millis(500);
+    let mut attempt = 0;
+
+    loop {
+        let err = match copy_item_pbr(path, target) {
+            Ok(copied) => return Ok(copied),
+            Err(err) => err,
+        };
+
+        if attempt >= retries || !is_transient_error(&err) {
+            return Err(err);
+        }
+
+        attempt += 1;
+
+        warn!(
+            "Transfer failed with a transient error, retrying in {:.1}s ({attempt}/{retries}): {err:#}",
+            delay.as_secs_f64()
+        );
+
+        if fs::symlink_metadata(target).is_ok() {
+            remove_item(target).with_context(|| {
+                format!(
+                    "Failed to remove partially transferred item: {}",
+                    target.display()
+                )
+            })?;
+        }
+
+        std::thread::sleep(delay);
+        delay *= 2;
+    }
+}
+
+/// Check if an error is caused by a transient I/O failure, which may not happen again on retry
+fn is_transient_error(err: &anyhow::Error) -> bool {
+    let is_transient_kind = |err: &io::Error| {
+        matches!(
+            err.kind(),
+            io::ErrorKind::Interrupted | io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+        )
+    };
+
+    err.chain().any(|cause| {
+        if let Some(err) = cause.downcast_ref::<io::Error>() {
+            return is_transient_kind(err);
+        }
+
+        match cause.downcast_ref::<fs_extra::error::Error>() {
+            Some(err) => match &err.kind {
+                fs_extra::error::ErrorKind::Interrupted => true,
+                fs_extra::error::ErrorKind::Io(err) => is_transient_kind(err),
+                _ => false,
+            },
+            None => false,
+        }
+    })
+}
+
+/// Copy items around with a progressbar, returning the number of bytes copied
+pub fn copy_item_pbr(path: &Path, target: &Path) -> Result<u64> {
+    let pbr = Rc::new(RefCell::new(None));
+    let percent = PercentProgress::new();
+
+    let update_pbr = |copied, total, item_name: &str| {
+        percent.update(copied, total);
+
+        let mut pbr = pbr.borrow_mut();
+        let pbr = pbr.get_or_insert_with(|| {
+            progress_bar(
+                total,
+                "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})",
+            )
+        });
+
+        pbr.set_position(copied);
+        pbr.set_message(item_name.to_string());
+    };
+
+    let copied = if fs::symlink_metadata(path)?.is_symlink() {
+        copy_symlink(path, target)?;
+        0
+    } else if path.metadata()?.is_file() {
+        // Try to clone the file first (copy-on-write), which is instant on filesystems
+        // supporting it (e.g. between subvolumes of the same Btrfs filesystem)
+        match reflink_copy::reflink(path, target) {
+            Ok(()) => {
+                debug!("Cloned file using reflink: {}", path.display());
+                return Ok(path.metadata()?.len());
+            }
+
+            Err(err) => {
+                debug!(
+                    "Failed to clone file using reflink, falling back to a full copy: {}",
+                    err
+                );
+            }
+        }
+
+        let file_name = path.file_name().unwrap().to_string_lossy();
+
+        fs_extra::file::copy_with_progress(
+            path,
+            target,
+            &fs_extra::file::CopyOptions::new(),
+            |tp| {
+                update_pbr(tp.copied_bytes, tp.total_bytes, &file_name);
+            },
+        )?
+    } else {
+        let mut config = fs_extra::dir::CopyOptions::new();
+        config.copy_inside = true;
+        fs_extra::dir::copy_with_progress(path, target, &config, |tp| {
+            update_pbr(tp.copied_bytes, tp.total_bytes, &tp.file_name);
+            TransitProcessResult::ContinueOrAbort
+        })?
+    };
+
+    let mut pbr = pbr.borrow_mut();
+    let pbr = pbr.as_mut();
+
+    if let Some(pbr) = pbr {
+        pbr.finish_with_message("Transfer complete.")
+    }
+
+    Ok(copied)
+}
+
+/// Remove an item, recursively if it's a directory
+pub fn remove_item(path: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// Display options for [`table_for_items`]
+#[derive(Default)]
+pub struct TableOptions {
+    pub owner: bool,
+    pub tags: bool,
+    pub host: bool,
+    pub original_path: bool,
+
+    /// Number of trailing components original paths are shortened to
+    pub path_depth: Option<usize>,
+
+    pub size_format: SizeFormat,
+
+    /// Score of each item, displayed in a dedicated column
+    pub scores: Option<Vec<usize>>,
+}
+
+pub fn table_for_items(items: &[TrashedItem], options: &TableOptions) -> Table {
+    let mut table = Table::new();
+
+    let mut header = vec!["Type", "Filename", "Size", "ID", "Deleted on"];
+
+    if options.owner {
+        header.push("By");
+    }
+
+    if options.host {
+        header.push("Host");
+    }
+
+    if options.original_path {
+        header.push("Original path");
+    }
+
+    if options.tags {
+        header.push("Tags");
+    }
+
+    if options.scores.is_some() {
+        header.push("Score");
+    }
+
+    header.push("Trash directory");
+
+    table
+        .load_preset(UTF8_FULL_CONDENSED)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(header);
+
+    for (i, item) in items.iter().enumerate() {
+        let TrashedItem {
+            data,
+            trash_dir,
+            pending_in,
+        } = item;
+
+        let item_path = item.complete_trash_item_path();
+        let mt = fs::symlink_metadata(&item_path);
+
+        let mut filename = if data.has_lossy_filename() {
+            format!("{} (invalid UTF-8)", data.filename_lossy())
+        } else {
+            data.filename_lossy().into_owned()
+        };
+
+        if !item.is_known_complete() {
+            filename.push_str(" (incomplete transfer)");
+        } else if pending_in.is_some() {
+            filename.push_str(" (pending)");
+        }
+
+        let mut row = vec![
+            describe_item_type(&item_path, &mt, data.metadata.original_path.as_deref()),
+            filename,
+            describe_item_size(&mt, options.size_format),
+            data.compute_id(),
+            format_date(data.datetime),
+        ];
+
+        if options.owner {
+            row.push(
+                data.metadata
+                    .owner
+                    .as_ref()
+                    .map(ItemOwner::to_string)
+                    .unwrap_or_default(),
+            );
+        }
+
+        if options.host {
+            row.push(data.metadata.hostname.clone().unwrap_or_default());
+        }
+
+        if options.original_path {
+            row.push(
+                data.metadata
+                    .original_path
+                    .as_deref()
+                    .map(|path| match options.path_depth {
+                        Some(depth) => shorten_path(path, depth),
+                        None => path.to_string_lossy().into_owned(),
+                    })
+                    .unwrap_or_default(),
+            );
+        }
+
+        if options.tags {
+            row.push(data.metadata.tags.join(", "));
+        }
+
+        if let Some(scores) = &options.scores {
+            row.push(scores.get(i).map(usize::to_string).unwrap_or_default());
+        }
+
+        row.push(trash_dir.to_string_lossy().into_owned());
+
+        table.add_row(row);
+    }
+
+    table
+}
+
+/// Render the content of a directory as an indented tree, down to the provided depth (if any)
+///
+/// Directories are suffixed with a `/`, and symbolic links are not followed.
+pub fn render_dir_tree(dir: &Path, max_depth: Option<usize>) -> Result<String> {
+    let mut tree = String::new();
+
+    let walker = WalkDir::new(dir)
+        .min_depth(1)
+        .max_depth(max_depth.unwrap_or(usize::MAX))
+        .follow_links(false)
+        .sort_by_file_name();
+
+    for entry in walker {
+        let entry = entry
+            .with_context(|| format!("Failed to read directory's content: {}", dir.display()))?;
+
+        let suffix = if entry.file_type().is_dir() { "/" } else { "" };
+
+        tree.push_str(&format!(
+            "{}{}{suffix}\n",
+            "  ".repeat(entry.depth()),
+            entry.file_name().to_string_lossy()
+        ));
+    }
+
+    if tree.is_empty() {
+        tree.push_str("  (empty)\n");
+    }
+
+    // Don't end with an empty line
+    tree.pop();
+
+    Ok(tree)
+}
+
+/// Only keep the last components of a path, replacing the other ones with `…` (e.g. `…/project/notes.txt`)
+pub fn shorten_path(path: &Path, depth: usize) -> String {
+    let components = path.components().collect::<Vec<_>>();
+
+    if components.len() <= depth {
+        return path.to_string_lossy().into_owned();
+    }
+
+    let kept = components[components.len() - depth..]
+        .iter()
+        .collect::<PathBuf>();
+
+    Path::new("…").join(kept).to_string_lossy().into_owned()
 }
 
-pub fn table_for_items(items: &[TrashedItem]) -> Table {
-    let mut table = Table::new();
+/// Placeholders supported in item templates
+const ITEM_TEMPLATE_PLACEHOLDERS: &[&str] = &["path", "name", "id", "date", "size"];
 
-    table
-        .load_preset(UTF8_FULL_CONDENSED)
-        .set_content_arrangement(ContentArrangement::Dynamic)
-        .set_header(vec![
-            "Type",
-            "Filename",
-            "Size",
-            "ID",
-            "Deleted on",
-            "Trash directory",
-        ]);
-
-    for item in items {
-        let TrashedItem { data, trash_dir } = item;
-
-        let TrashItemInfos { filename, datetime } = data;
-
-        let mt = fs::metadata(item.complete_trash_item_path());
-
-        table.add_row(vec![
-            mt.as_ref()
-                .map(|mt| {
-                    if mt.file_type().is_file() {
-                        "File"
-                    } else if mt.file_type().is_dir() {
-                        "Directory"
-                    } else {
-                        "<Unknown>"
+/// Validate a template describing an item (see [`expand_item_template`])
+pub fn parse_item_template(template: &str) -> Result<String, String> {
+    expand_template(template, |placeholder| {
+        ITEM_TEMPLATE_PLACEHOLDERS
+            .contains(&placeholder)
+            .then(String::new)
+    })?;
+
+    Ok(template.to_owned())
+}
+
+/// Expand a template describing an item
+///
+/// Supported placeholders are `{path}`, `{name}`, `{id}`, `{date}` (RFC 3339) and `{size}` (in bytes).
+/// Literal braces are written `{{` and `}}`.
+pub fn expand_item_template(template: &str, item: &TrashedItem, path: &str) -> Result<String> {
+    let item_path = item.complete_trash_item_path();
+
+    let size = if template.contains("{size}") {
+        item_size(&item_path)?
+    } else {
+        0
+    };
+
+    expand_template(template, |placeholder| match placeholder {
+        "path" => Some(path.to_owned()),
+        "name" => Some(item.data.filename_lossy().into_owned()),
+        "id" => Some(item.data.compute_id()),
+        "date" => Some(
+            jiff::Timestamp::try_from(item.data.datetime)
+                .map(|date| date.to_string())
+                .unwrap_or_default(),
+        ),
+        "size" => Some(size.to_string()),
+        _ => None,
+    })
+    .map_err(anyhow::Error::msg)
+}
+
+fn expand_template(
+    template: &str,
+    resolve: impl Fn(&str) -> Option<String>,
+) -> Result<String, String> {
+    let mut out = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+
+            '{' => {
+                let mut placeholder = String::new();
+
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => placeholder.push(c),
+                        None => return Err("Unclosed '{' in template".to_owned()),
                     }
-                    .to_owned()
-                })
-                .unwrap_or_else(|err| format!("ERROR: {err}")),
-            filename.clone(),
-            mt.as_ref()
-                .map(|mt| {
-                    if mt.file_type().is_file() {
-                        human_readable_size(mt.len())
-                    } else {
-                        String::new()
+                }
+
+                let value = resolve(&placeholder).ok_or_else(|| {
+                    format!("Unknown placeholder '{{{placeholder}}}' in template")
+                })?;
+
+                out.push_str(&value);
+            }
+
+            '}' => return Err("Unmatched '}' in template (use '}}' for a literal one)".to_owned()),
+
+            _ => out.push(c),
+        }
+    }
+
+    Ok(out)
+}
+
+/// Machine-readable description of a trash item
+#[derive(Serialize)]
+pub struct ItemDescription {
+    pub filename: String,
+    pub invalid_utf8_filename: bool,
+    pub id: String,
+    pub item_type: &'static str,
+    pub size: Option<u64>,
+    pub deleted_on: String,
+    pub owner: Option<ItemOwner>,
+    pub hostname: Option<String>,
+    pub original_path: Option<PathBuf>,
+    pub tags: Vec<String>,
+    pub trash_dir: PathBuf,
+    pub pending: bool,
+    /// Left in a transfer directory by an interrupted transfer, and may be an incomplete copy
+    pub incomplete: bool,
+}
+
+impl ItemDescription {
+    pub fn new(item: &TrashedItem) -> Self {
+        let TrashedItem {
+            data,
+            trash_dir,
+            pending_in,
+        } = item;
+
+        let mt = fs::symlink_metadata(item.complete_trash_item_path()).ok();
+
+        Self {
+            filename: data.filename_lossy().into_owned(),
+            invalid_utf8_filename: data.has_lossy_filename(),
+            id: data.compute_id(),
+            item_type: match &mt {
+                Some(mt) if mt.is_file() => "file",
+                Some(mt) if mt.is_dir() => "directory",
+                Some(mt) if mt.is_symlink() => "symlink",
+                _ => "unknown",
+            },
+            size: mt.filter(|mt| mt.is_file()).map(|mt| mt.len()),
+            deleted_on: jiff::Timestamp::try_from(data.datetime)
+                .map(|date| date.to_string())
+                .unwrap_or_default(),
+            owner: data.metadata.owner.clone(),
+            hostname: data.metadata.hostname.clone(),
+            original_path: data.metadata.original_path.clone(),
+            tags: data.metadata.tags.clone(),
+            trash_dir: trash_dir.clone(),
+            pending: pending_in.is_some(),
+            incomplete: !item.is_known_complete(),
+        }
+    }
+}
+
+/// Render a list of items as JSON
+pub fn json_for_items(items: &[TrashedItem]) -> Result<String> {
+    let items = items.iter().map(ItemDescription::new).collect::<Vec<_>>();
+
+    serde_json::to_string_pretty(&items).context("Failed to serialize items")
+}
+
+/// Describe the type of an item from its metadata (not following symbolic links)
+///
+/// Relative symbolic links are resolved from the provided original path's parent, as resolving
+/// them from the trash directory would be meaningless. Without it, they aren't told as broken or not.
+pub fn describe_item_type(
+    path: &Path,
+    mt: &io::Result<Metadata>,
+    original_path: Option<&Path>,
+) -> String {
+    mt.as_ref()
+        .map(|mt| {
+            if mt.file_type().is_file() {
+                "File".to_owned()
+            } else if mt.file_type().is_dir() {
+                "Directory".to_owned()
+            } else if mt.file_type().is_symlink() {
+                let Ok(target) = fs::read_link(path) else {
+                    return "Symlink".to_owned();
+                };
+
+                let resolved = if target.is_absolute() {
+                    Some(target.clone())
+                } else {
+                    original_path
+                        .and_then(Path::parent)
+                        .map(|parent| parent.join(&target))
+                };
+
+                match resolved {
+                    Some(resolved) if !resolved.exists() => {
+                        format!("Broken symlink -> {}", target.display())
                     }
-                })
-                .unwrap_or_else(|_| "ERROR".to_owned()),
-            data.compute_id(),
-            Zoned::try_from(*datetime)
-                .and_then(|date| jiff::fmt::rfc2822::to_string(&date))
-                .unwrap_or_else(|_| "<Failed to format date>".to_owned()),
-            trash_dir.to_string_lossy().into_owned(),
-        ]);
+                    _ => format!("Symlink -> {}", target.display()),
+                }
+            } else {
+                "<Unknown>".to_owned()
+            }
+        })
+        .unwrap_or_else(|err| format!("ERROR: {err}"))
+}
+
+/// Describe the size of an item from its metadata (empty for directories)
+pub fn describe_item_size(mt: &io::Result<Metadata>, format: SizeFormat) -> String {
+    mt.as_ref()
+        .map(|mt| {
+            if mt.file_type().is_file() {
+                human_readable_size(mt.len(), format)
+            } else {
+                String::new()
+            }
+        })
+        .unwrap_or_else(|_| "ERROR".to_owned())
+}
+
+/// Format a deletion date for display
+pub fn format_date(datetime: SystemTime) -> String {
+    Zoned::try_from(datetime)
+        .and_then(|date| jiff::fmt::rfc2822::to_string(&date))
+        .unwrap_or_else(|_| "<Failed to format date>".to_owned())
+}
+
+/// Format a date in a specific time zone
+pub fn format_date_in(datetime: SystemTime, time_zone: TimeZone) -> String {
+    Timestamp::try_from(datetime)
+        .and_then(|timestamp| jiff::fmt::rfc2822::to_string(&timestamp.to_zoned(time_zone)))
+        .unwrap_or_else(|_| "<Failed to format date>".to_owned())
+}
+
+/// Flush a file or directory to the disk, making previous writes and renames inside it durable
+///
+/// Directories can't be flushed on Windows, so they are ignored there.
+pub fn sync_path(path: &Path) -> Result<()> {
+    #[cfg(target_family = "windows")]
+    if path.is_dir() {
+        return Ok(());
     }
 
-    table
+    fs::File::open(path)
+        .and_then(|file| file.sync_all())
+        .with_context(|| format!("Failed to sync item to the disk: {}", path.display()))
+}
+
+/// Device IDs of the destination directories checked by [`are_on_same_fs`] during the current action
+///
+/// Must be cleared with [`clear_device_ids_cache`] between actions of a long-lived process,
+/// as a filesystem may be mounted or unmounted in the meantime.
+static DEVICE_IDS_CACHE: LazyLock<Mutex<HashMap<PathBuf, u64>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Forget the device IDs cached by [`are_on_same_fs`]
+pub fn clear_device_ids_cache() {
+    DEVICE_IDS_CACHE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clear();
 }
 
+/// Check if two items are located on the same filesystem
+///
+/// The first item is not followed if it's a symbolic link, as the link itself is what gets moved.
+///
+/// The second one is a destination directory (e.g. a trash directory), whose device ID is cached
+/// as it's usually the same one for many items.
 pub fn are_on_same_fs(a: &Path, b: &Path) -> Result<bool> {
-    fn get_dev(item: &Path) -> Result<u64> {
-        let mt = fs::metadata(item)?;
+    let a_fs_id = get_dev(fs::symlink_metadata(a))
+        .with_context(|| format!("Failed to get filesystem ID for item '{}'", a.display()))?;
 
-        #[cfg(target_family = "windows")]
-        {
-            use std::os::windows::fs::MetadataExt;
-            mt.volume_serial_number()
-                .map(u64::from)
-                .context("Item does not have a volume serial number attached")
+    let cached_b_fs_id = DEVICE_IDS_CACHE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(b)
+        .copied();
+
+    let b_fs_id = match cached_b_fs_id {
+        Some(b_fs_id) => b_fs_id,
+        None => {
+            let b_fs_id = get_dev(fs::metadata(b)).with_context(|| {
+                format!("Failed to get filesystem ID for item '{}'", b.display())
+            })?;
+
+            DEVICE_IDS_CACHE
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .insert(b.to_owned(), b_fs_id);
+
+            b_fs_id
         }
+    };
 
-        #[cfg(target_family = "unix")]
-        {
-            use std::os::unix::fs::MetadataExt;
-            Ok(mt.dev())
+    debug!(
+        "Device ID of '{}': {}, device ID of '{}': {}",
+        a.display(),
+        a_fs_id,
+        b.display(),
+        b_fs_id
+    );
+
+    Ok(a_fs_id == b_fs_id)
+}
+
+/// Get the ID of the filesystem an item is located on
+fn get_dev(mt: io::Result<Metadata>) -> Result<u64> {
+    let mt = mt?;
+
+    #[cfg(target_family = "windows")]
+    {
+        use std::os::windows::fs::MetadataExt;
+        mt.volume_serial_number()
+            .map(u64::from)
+            .context("Item does not have a volume serial number attached")
+    }
+
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Ok(mt.dev())
+    }
+}
+
+/// Describe the location of a trash directory, including the filesystem it's located on
+///
+/// Used in error messages, so users with multiple disks know which one is affected.
+pub fn describe_trash_dir_location(trash_dir: &Path) -> String {
+    let mountpoint = mountpaths().ok().and_then(|mountpoints| {
+        mountpoints
+            .into_iter()
+            .filter(|mountpoint| trash_dir.starts_with(mountpoint))
+            .max_by_key(|mountpoint| mountpoint.components().count())
+    });
+
+    match mountpoint {
+        Some(mountpoint) => format!(
+            "trash directory '{}' (on the filesystem mounted at '{}')",
+            trash_dir.display(),
+            mountpoint.display()
+        ),
+        None => format!("trash directory '{}'", trash_dir.display()),
+    }
+}
+
+/// Find an item inside a directory that's located on another filesystem (e.g. a mount point)
+pub fn find_nested_mount_point(dir: &Path) -> Result<Option<PathBuf>> {
+    let dir_fs_id = get_dev(fs::symlink_metadata(dir))
+        .with_context(|| format!("Failed to get filesystem ID for item '{}'", dir.display()))?;
+
+    for entry in WalkDir::new(dir).min_depth(1).follow_links(false) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) if err.loop_ancestor().is_some() => continue,
+            Err(err) => return Err(err).context("Failed to read directory entry"),
+        };
+
+        let fs_id = get_dev(fs::symlink_metadata(entry.path())).with_context(|| {
+            format!(
+                "Failed to get filesystem ID for item '{}'",
+                entry.path().display()
+            )
+        })?;
+
+        if fs_id != dir_fs_id {
+            return Ok(Some(entry.into_path()));
         }
     }
 
-    let a_fs_id = get_dev(a)
-        .with_context(|| format!("Failed to get filesystem ID for item '{}'", a.display()))?;
+    Ok(None)
+}
 
-    let b_fs_id = get_dev(b)
-        .with_context(|| format!("Failed to get filesystem ID for item '{}'", b.display()))?;
+/// Create a symbolic link pointing to the same target as another one
+fn copy_symlink(path: &Path, target: &Path) -> Result<()> {
+    let link_target = fs::read_link(path)
+        .with_context(|| format!("Failed to read symbolic link: {}", path.display()))?;
 
-    Ok(a_fs_id == b_fs_id)
+    #[cfg(target_family = "unix")]
+    let result = std::os::unix::fs::symlink(&link_target, target);
+
+    #[cfg(target_family = "windows")]
+    let result = if path.is_dir() {
+        std::os::windows::fs::symlink_dir(&link_target, target)
+    } else {
+        std::os::windows::fs::symlink_file(&link_target, target)
+    };
+
+    result.with_context(|| format!("Failed to create symbolic link: {}", target.display()))
+}
+
+/// Get the absolute path of an item, with its parent directory canonicalized
+///
+/// The item itself is not canonicalized, so symbolic links are not resolved.
+pub fn absolute_item_path(path: &Path) -> Result<PathBuf> {
+    let (Some(parent), Some(filename)) = (path.parent(), path.file_name()) else {
+        return fs::canonicalize(path)
+            .with_context(|| format!("Failed to canonicalize path: {}", path.display()));
+    };
+
+    let parent = if parent.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        parent
+    };
+
+    let parent = fs::canonicalize(parent)
+        .with_context(|| format!("Failed to canonicalize path: {}", parent.display()))?;
+
+    Ok(parent.join(filename))
+}
+
+/// Get the absolute path of an item that may not exist, by canonicalizing its nearest existing
+/// ancestor and appending the remaining components to it
+pub fn lenient_absolute_path(path: &Path) -> Result<PathBuf> {
+    let path = if path.is_absolute() {
+        path.to_owned()
+    } else {
+        std::env::current_dir()
+            .context("Failed to determine path to the current directory")?
+            .join(path)
+    };
+
+    for ancestor in path.ancestors() {
+        if fs::symlink_metadata(ancestor).is_err() {
+            continue;
+        }
+
+        let remainder = path.strip_prefix(ancestor).unwrap();
+
+        if remainder.as_os_str().is_empty() {
+            return absolute_item_path(&path);
+        }
+
+        let ancestor = fs::canonicalize(ancestor)
+            .with_context(|| format!("Failed to canonicalize path: {}", ancestor.display()))?;
+
+        return Ok(ancestor.join(remainder));
+    }
+
+    bail!("No ancestor of path '{}' exists", path.display())
+}
+
+/// Check if a directory is located on a case-sensitive filesystem
+///
+/// This is determined without writing anything, by accessing one of the directory's entries
+/// (or the directory itself if it has none) through its name with a different case.
+/// Directories without any entry whose case can be changed can't conflict with another name
+/// because of their case, so they're considered case-sensitive.
+pub fn is_case_sensitive_fs(dir: &Path) -> Result<bool> {
+    let mut candidate = None;
+
+    for entry in
+        fs::read_dir(dir).with_context(|| format!("Failed to read directory: {}", dir.display()))?
+    {
+        let entry = entry.context("Failed to read directory entry")?;
+
+        if swap_case(&entry.file_name()).is_some() {
+            candidate = Some(entry.path());
+            break;
+        }
+    }
+
+    let path = candidate.unwrap_or_else(|| dir.to_owned());
+
+    let (Some(parent), Some(swapped_name)) = (path.parent(), path.file_name().and_then(swap_case))
+    else {
+        return Ok(true);
+    };
+
+    let swapped_path = parent.join(swapped_name);
+
+    let is_same = match is_same_item(&path, &swapped_path) {
+        Ok(is_same) => is_same,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => false,
+        Err(err) => {
+            return Err(err).with_context(|| {
+                format!(
+                    "Failed to check case sensitivity of directory: {}",
+                    dir.display()
+                )
+            })
+        }
+    };
+
+    Ok(!is_same)
+}
+
+/// Swap the case of a name's letters, if it has any whose case can be changed
+fn swap_case(name: &OsStr) -> Option<String> {
+    let name = name.to_str()?;
+
+    let swapped = name
+        .chars()
+        .map(|c| {
+            if c.is_lowercase() {
+                c.to_uppercase().collect::<String>()
+            } else {
+                c.to_lowercase().collect::<String>()
+            }
+        })
+        .collect::<String>();
+
+    (swapped != name).then_some(swapped)
+}
+
+/// Check if two paths point to the same item (without following symbolic links)
+fn is_same_item(a: &Path, b: &Path) -> io::Result<bool> {
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        let (a, b) = (fs::symlink_metadata(a)?, fs::symlink_metadata(b)?);
+        Ok(a.dev() == b.dev() && a.ino() == b.ino())
+    }
+
+    // Canonicalized paths use the case of the items' actual names
+    #[cfg(target_family = "windows")]
+    {
+        Ok(fs::canonicalize(a)? == fs::canonicalize(b)?)
+    }
+}
+
+/// Find an entry of a directory with the same name as the provided one, ignoring case
+pub fn find_entry_ignoring_case(dir: &Path, name: &OsStr) -> Result<Option<PathBuf>> {
+    let name = name.to_string_lossy().to_lowercase();
+
+    for entry in
+        fs::read_dir(dir).with_context(|| format!("Failed to read directory: {}", dir.display()))?
+    {
+        let entry = entry.context("Failed to read directory entry")?;
+
+        if entry.file_name().to_string_lossy().to_lowercase() == name {
+            return Ok(Some(entry.path()));
+        }
+    }
+
+    Ok(None)
 }
 
+/// List all items inside a directory, with each directory's content listed before itself
+///
+/// The order comes from the directory walk itself rather than from sorting the paths, so it holds
+/// regardless of how names compare to each other (e.g. with siblings named `a` and `a b`).
+///
+/// Symbolic links are never followed so they can be removed without affecting their target.
 pub fn list_deletable_fs_items(path: &Path) -> Result<Vec<PathBuf>> {
     WalkDir::new(path)
+        .follow_links(false)
         .contents_first(true)
         .into_iter()
+        .filter(|entry| !matches!(entry, Err(err) if err.loop_ancestor().is_some()))
         .map(|entry| entry.map(|entry| entry.into_path()))
         .collect::<Result<Vec<PathBuf>, _>>()
         .context("Failed to read directory entry")
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use clap::Parser;
+
+    use super::*;
+
+    fn dir_entries(dir: &Path) -> Vec<PathBuf> {
+        let mut entries = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>();
+
+        entries.sort();
+        entries
+    }
+
+    #[test]
+    fn human_readable_sizes() {
+        assert_eq!(human_readable_size(0, SizeFormat::Iec), "0 B");
+        assert_eq!(human_readable_size(1000, SizeFormat::Iec), "1000 B");
+        assert_eq!(human_readable_size(1023, SizeFormat::Iec), "1023 B");
+        assert_eq!(human_readable_size(1024, SizeFormat::Iec), "1.00 KiB");
+        assert_eq!(human_readable_size(1536, SizeFormat::Iec), "1.50 KiB");
+        assert_eq!(
+            human_readable_size((1 << 20) - 1, SizeFormat::Iec),
+            "1.00 MiB"
+        );
+        assert_eq!(human_readable_size(1 << 30, SizeFormat::Iec), "1.00 GiB");
+
+        assert_eq!(human_readable_size(999, SizeFormat::Si), "999 B");
+        assert_eq!(human_readable_size(1000, SizeFormat::Si), "1.00 kB");
+        assert_eq!(human_readable_size(1023, SizeFormat::Si), "1.02 kB");
+        assert_eq!(human_readable_size(1024, SizeFormat::Si), "1.02 kB");
+        assert_eq!(human_readable_size(1_500_000, SizeFormat::Si), "1.50 MB");
+    }
+
+    #[test]
+    fn parse_sizes() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("512B"), Ok(512));
+        assert_eq!(parse_size("1K"), Ok(1024));
+        assert_eq!(parse_size("1KiB"), Ok(1024));
+        assert_eq!(parse_size("1kB"), Ok(1000));
+        assert_eq!(parse_size("1KB"), Ok(1000));
+        assert_eq!(parse_size("10MB"), Ok(10_000_000));
+        assert_eq!(parse_size("100MiB"), Ok(100 << 20));
+        assert_eq!(parse_size("1.5G"), Ok(3 << 29));
+        assert_eq!(parse_size(" 2 TiB "), Ok(2 << 40));
+
+        assert!(parse_size("").is_err());
+        assert!(parse_size("MB").is_err());
+        assert!(parse_size("1kb").is_err());
+        assert!(parse_size("1 parsec").is_err());
+    }
+
+    /// Check that each directory is listed after all of its content, and that every item is listed
+    fn assert_contents_first(root: &Path, items: &[PathBuf], expected_len: usize) {
+        assert_eq!(items.len(), expected_len);
+        assert_eq!(items.last().map(PathBuf::as_path), Some(root));
+
+        for (i, item) in items.iter().enumerate() {
+            for other in &items[i + 1..] {
+                assert!(
+                    !other.starts_with(item),
+                    "'{}' is listed before its content '{}'",
+                    item.display(),
+                    other.display()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn deletable_items_with_special_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+
+        for name in ["a", "a b", "report ^AbC", "-rf", "--help"] {
+            fs::create_dir_all(root.join(name).join("sub dir")).unwrap();
+            fs::write(root.join(name).join("file"), "").unwrap();
+            fs::write(root.join(name).join("sub dir").join("-file ^x"), "").unwrap();
+        }
+
+        let items = list_deletable_fs_items(&root).unwrap();
+
+        // Each directory contains 'file', 'sub dir' and 'sub dir/-file ^x'
+        assert_contents_first(&root, &items, 5 * 4 + 1);
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn deletable_items_with_non_utf8_names() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+
+        let invalid = OsStr::from_bytes(b"invalid \xff\xfe name");
+        let sub_dir = root.join(invalid);
+
+        fs::create_dir_all(&sub_dir).unwrap();
+        fs::write(sub_dir.join(OsStr::from_bytes(b"\xc3(")), "").unwrap();
+        fs::write(root.join("valid"), "").unwrap();
+
+        let items = list_deletable_fs_items(&root).unwrap();
+
+        assert_contents_first(&root, &items, 4);
+        assert!(items.contains(&sub_dir));
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn recursive_walks_with_symlink_cycles() {
+        use std::os::unix::fs::symlink;
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("sub").join("file"), "12345").unwrap();
+        symlink("..", root.join("sub").join("parent")).unwrap();
+        symlink(".", root.join("self")).unwrap();
+        fs::write(dir.path().join("outside"), "content").unwrap();
+
+        // Only the symbolic links' own size is counted
+        let size = item_size(&root).unwrap();
+        assert!((5..5 + 4096 * 4).contains(&size));
+
+        let items = list_deletable_fs_items(&root).unwrap();
+        assert_contents_first(&root, &items, 5);
+
+        remove_item(&root).unwrap();
+        assert!(fs::symlink_metadata(&root).is_err());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("outside")).unwrap(),
+            "content"
+        );
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn mountpoints_with_symlinked_home() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = fs::canonicalize(dir.path()).unwrap();
+
+        let home_dir = dir.join("var").join("home");
+        fs::create_dir_all(&home_dir).unwrap();
+        std::os::unix::fs::symlink(dir.join("var"), dir.join("link")).unwrap();
+
+        let mountpoints = canonical_mountpoints_with_home(
+            vec![dir.join("link").join("home"), dir.join("nonexistent")],
+            &home_dir,
+        );
+
+        assert_eq!(mountpoints, [home_dir.clone(), dir.join("nonexistent")]);
+
+        let mountpoints = canonical_mountpoints_with_home(vec![dir.join("nonexistent")], &home_dir);
+
+        assert_eq!(mountpoints, [dir.join("nonexistent"), home_dir]);
+    }
+
+    /// Create an item in the transfer directory of a trash, as if its transfer was interrupted
+    fn strand_transfer(trash_dir: &Path, original_path: &Path, config: &Config) -> TrashedItem {
+        let mut data = TrashItemInfos::new_now(original_path.file_name().unwrap().to_owned());
+        data.metadata.original_path = Some(original_path.to_owned());
+
+        let item = TrashedItem {
+            data,
+            trash_dir: trash_dir.to_owned(),
+            pending_in: None,
+        };
+
+        let transfer_path = item.transfer_trash_item_path(config);
+        fs::create_dir_all(transfer_path.parent().unwrap()).unwrap();
+        fs::write(&transfer_path, "content").unwrap();
+        item.write_metadata(false).unwrap();
+
+        item
+    }
+
+    #[test]
+    fn complete_partial_transfer_is_moved_to_trash() {
+        let dir = tempfile::tempdir().unwrap();
+        let trash_dir = dir.path().join("trash");
+        let config = Config::for_tests(&trash_dir, &[]);
+
+        // The original item was removed, so the copy is complete
+        let item = strand_transfer(&trash_dir, &dir.path().join("report.txt"), &config);
+
+        let issues = find_trash_issues(&trash_dir, &config).unwrap();
+        assert!(matches!(
+            issues[..],
+            [TrashIssue::CompletePartialTransfer(_)]
+        ));
+
+        fix_trash_issue(&trash_dir, &issues[0]).unwrap();
+
+        let items = list_trash_items(&trash_dir, &config).unwrap();
+        assert_eq!(items.len(), 1);
+        assert!(!items[0].is_pending());
+        assert_eq!(items[0].data.compute_id(), item.data.compute_id());
+        assert!(items[0].data.metadata.original_path.is_some());
+
+        assert!(find_trash_issues(&trash_dir, &config).unwrap().is_empty());
+    }
+
+    #[test]
+    fn incomplete_partial_transfer_is_quarantined() {
+        let dir = tempfile::tempdir().unwrap();
+        let trash_dir = dir.path().join("trash");
+        let config = Config::for_tests(&trash_dir, &[]);
+
+        // The original item still exists, so the copy may be incomplete
+        let original_path = dir.path().join("report.txt");
+        fs::write(&original_path, "content").unwrap();
+
+        let item = strand_transfer(&trash_dir, &original_path, &config);
+
+        let issues = find_trash_issues(&trash_dir, &config).unwrap();
+        assert!(matches!(
+            &issues[..],
+            [TrashIssue::PartialTransfer(_, Some(path))] if path == &original_path
+        ));
+
+        fix_trash_issue(&trash_dir, &issues[0]).unwrap();
+
+        assert!(list_trash_items(&trash_dir, &config).unwrap().is_empty());
+        assert!(trash_dir
+            .join(TRASH_QUARANTINE_DIRNAME)
+            .join(item.data.trash_filename())
+            .exists());
+
+        assert!(find_trash_issues(&trash_dir, &config).unwrap().is_empty());
+    }
+
+    #[test]
+    fn potential_trash_dirs_are_listed_separately() {
+        let dir = tempfile::tempdir().unwrap();
+        let trash_dir = dir.path().join("trash");
+        let config = Config::for_tests(&trash_dir, &[]);
+
+        // Trash directories that don't exist yet are only listed as potential ones
+        assert!(list_trash_dirs(&config).unwrap().is_empty());
+        assert_eq!(
+            list_potential_trash_dirs(&config).unwrap(),
+            BTreeSet::from([trash_dir.clone()])
+        );
+
+        fs::create_dir(&trash_dir).unwrap();
+
+        assert_eq!(
+            list_trash_dirs(&config).unwrap(),
+            BTreeSet::from([trash_dir])
+        );
+    }
+
+    #[test]
+    fn trash_dir_path_taken_by_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let trash_dir = dir.path().join("trash");
+        fs::write(&trash_dir, "").unwrap();
+
+        let config = Config::for_tests(&trash_dir, &[]);
+
+        let err = ensure_trash_dir_is_dir(&trash_dir).unwrap_err();
+        assert!(err.to_string().contains("exists but is not a directory"));
+
+        assert!(list_trash_items(&trash_dir, &config).is_err());
+
+        // Listing all items skips the invalid trash directory instead of failing
+        assert!(list_trash_dirs(&config).unwrap().is_empty());
+        assert!(list_all_trash_items(&config).unwrap().is_empty());
+
+        ensure_trash_dir_is_dir(dir.path()).unwrap();
+        ensure_trash_dir_is_dir(&dir.path().join("nonexistent")).unwrap();
+    }
+
+    #[test]
+    fn lenient_absolute_path_of_deep_nonexistent_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let canon_dir = fs::canonicalize(dir.path()).unwrap();
+
+        let deep = Path::new("a").join("b c").join("d").join("e.txt");
+
+        assert_eq!(
+            lenient_absolute_path(&dir.path().join(&deep)).unwrap(),
+            canon_dir.join(&deep)
+        );
+
+        // Existing items are resolved like with `absolute_item_path`
+        fs::create_dir(dir.path().join("a")).unwrap();
+
+        assert_eq!(
+            lenient_absolute_path(&dir.path().join("a")).unwrap(),
+            absolute_item_path(&dir.path().join("a")).unwrap()
+        );
+
+        assert_eq!(
+            lenient_absolute_path(&dir.path().join(&deep)).unwrap(),
+            canon_dir.join(&deep)
+        );
+    }
+
+    #[test]
+    fn archive_formats() {
+        assert!(matches!(
+            ArchiveFormat::from_path(Path::new("/tmp/report.tar")),
+            Ok(ArchiveFormat::Tar)
+        ));
+        assert!(matches!(
+            ArchiveFormat::from_path(Path::new("report.TAR.GZ")),
+            Ok(ArchiveFormat::TarGz)
+        ));
+        assert!(matches!(
+            ArchiveFormat::from_path(Path::new("report.tgz")),
+            Ok(ArchiveFormat::TarGz)
+        ));
+
+        for unsupported in [
+            "report.zip",
+            "report.tar.zst",
+            "report.tzst",
+            "report.7z",
+            "report",
+        ] {
+            let err = ArchiveFormat::from_path(Path::new(unsupported))
+                .err()
+                .unwrap();
+            assert!(err.to_string().contains(".tar, .tar.gz and .tgz"));
+        }
+
+        let err = ArchiveFormat::from_path(Path::new("report.zip"))
+            .err()
+            .unwrap();
+        assert!(err
+            .to_string()
+            .starts_with("ZIP archives are not supported"));
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn lenient_absolute_path_through_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let canon_dir = fs::canonicalize(dir.path()).unwrap();
+
+        fs::create_dir(dir.path().join("real")).unwrap();
+        std::os::unix::fs::symlink(dir.path().join("real"), dir.path().join("link")).unwrap();
+
+        assert_eq!(
+            lenient_absolute_path(&dir.path().join("link").join("x").join("y")).unwrap(),
+            canon_dir.join("real").join("x").join("y")
+        );
+    }
+
+    #[test]
+    fn nonexistent_exclusion_is_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = fs::canonicalize(dir.path()).unwrap();
+        let excluded = dir.join("mnt").join("drive");
+
+        let config = crate::args::Opts::parse_from([
+            "trasher".as_ref(),
+            "--exclude".as_ref(),
+            excluded.as_os_str(),
+            "ls".as_ref(),
+        ])
+        .config;
+
+        assert!(compute_exclusions(&config).unwrap().contains(&excluded));
+
+        assert_eq!(
+            determine_trash_dir_for_lenient(&excluded.join("file"), &config).unwrap(),
+            home_trash_dir(&config).unwrap()
+        );
+    }
+
+    #[test]
+    fn device_ids_cache_is_cleared() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("file");
+        fs::write(&file, "").unwrap();
+
+        assert!(are_on_same_fs(&file, dir.path()).unwrap());
+
+        let is_cached = || {
+            DEVICE_IDS_CACHE
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .contains_key(dir.path())
+        };
+
+        assert!(is_cached());
+        clear_device_ids_cache();
+        assert!(!is_cached());
+    }
+
+    #[test]
+    fn case_sensitivity_check_does_not_write() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("report.txt"), "").unwrap();
+
+        let before = dir_entries(dir.path());
+        let case_sensitive = is_case_sensitive_fs(dir.path()).unwrap();
+
+        assert_eq!(dir_entries(dir.path()), before);
+
+        // Linux filesystems used for tests are case-sensitive
+        if cfg!(target_os = "linux") {
+            assert!(case_sensitive);
+        }
+    }
+
+    #[test]
+    fn case_sensitivity_check_with_names_differing_by_case() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("report.txt"), "a").unwrap();
+
+        if fs::symlink_metadata(dir.path().join("REPORT.TXT")).is_ok() {
+            // Case-insensitive filesystem
+            assert!(!is_case_sensitive_fs(dir.path()).unwrap());
+            return;
+        }
+
+        fs::write(dir.path().join("REPORT.TXT"), "b").unwrap();
+        assert!(is_case_sensitive_fs(dir.path()).unwrap());
+    }
+
+    #[test]
+    fn case_sensitivity_check_in_empty_dir() {
+        let dir = tempfile::tempdir().unwrap();
+
+        is_case_sensitive_fs(dir.path()).unwrap();
+        assert!(dir_entries(dir.path()).is_empty());
+    }
+}
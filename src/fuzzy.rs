@@ -1,15 +1,16 @@
-use std::io;
+use std::{io, ops::Range, time::SystemTime};
 
 use anyhow::{bail, Result};
 use crossterm::{
-    event::{self, Event, KeyCode},
+    event::{self, Event, KeyCode, KeyModifiers},
     terminal::{self, disable_raw_mode},
     ExecutableCommand,
 };
 use ratatui::{
     layout::Position,
     prelude::{Backend, Constraint, CrosstermBackend, Direction, Layout},
-    style::{Color, Style},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
     widgets::{List, ListItem, ListState, Paragraph},
     Frame, Terminal,
 };
@@ -19,9 +20,52 @@ use tui_input::{backend::crossterm::EventHandler, Input};
 pub struct FuzzyFinderItem<T: Clone> {
     pub value: T,
     pub display: String,
+    pub date: SystemTime,
 }
 
-pub fn run_fuzzy_finder<T: Clone>(list: Vec<FuzzyFinderItem<T>>) -> Result<T> {
+/// Check if a query should be matched case-sensitively
+///
+/// Uses smart-case: the query is case-insensitive unless it contains an uppercase letter
+pub fn is_case_sensitive(query: &str, ignore_case: bool) -> bool {
+    !ignore_case && query.chars().any(char::is_uppercase)
+}
+
+/// Find the first occurrence of a query in a subject, returning its byte range in the subject
+pub fn find_match(subject: &str, query: &str, case_sensitive: bool) -> Option<Range<usize>> {
+    if case_sensitive {
+        return subject.find(query).map(|pos| pos..pos + query.len());
+    }
+
+    subject.char_indices().find_map(|(start, _)| {
+        let mut subject_chars = subject[start..].char_indices();
+        let mut end = start;
+
+        for query_char in query.chars() {
+            let (offset, subject_char) = subject_chars.next()?;
+
+            if !subject_char.to_lowercase().eq(query_char.to_lowercase()) {
+                return None;
+            }
+
+            end = start + offset + subject_char.len_utf8();
+        }
+
+        Some(start..end)
+    })
+}
+
+/// Let the user pick an item from a list
+///
+/// If a limit is provided, only the first (or best matching) items are displayed,
+/// but queries still match the whole list.
+///
+/// Pressing `Ctrl+T` toggles sorting by date: newest items come first when the query is empty,
+/// and items with the same score are sorted from newest to oldest otherwise.
+pub fn run_fuzzy_finder<T: Clone>(
+    list: Vec<FuzzyFinderItem<T>>,
+    ignore_case: bool,
+    limit: Option<usize>,
+) -> Result<T> {
     crossterm::terminal::enable_raw_mode()?;
 
     let mut stdout = io::stdout();
@@ -42,6 +86,9 @@ pub fn run_fuzzy_finder<T: Clone>(list: Vec<FuzzyFinderItem<T>>) -> Result<T> {
             list,
             list_state: ListState::default(),
             filtered: vec![],
+            ignore_case,
+            limit,
+            sort_by_date: false,
         },
     );
 
@@ -59,7 +106,13 @@ pub fn run_fuzzy_finder<T: Clone>(list: Vec<FuzzyFinderItem<T>>) -> Result<T> {
 
 fn run_app<B: Backend, T: Clone>(terminal: &mut Terminal<B>, mut state: State<T>) -> Result<T> {
     loop {
-        state.filtered = fuzzy_find_match(state.input_widget.value(), &state.list);
+        state.filtered = fuzzy_find_match(
+            state.input_widget.value(),
+            &state.list,
+            state.ignore_case,
+            state.limit,
+            state.sort_by_date,
+        );
 
         match state.list_state.selected() {
             Some(selected) => {
@@ -83,7 +136,7 @@ fn run_app<B: Backend, T: Clone>(terminal: &mut Terminal<B>, mut state: State<T>
             match key.code {
                 KeyCode::Enter => {
                     if let Some(selected) = state.list_state.selected() {
-                        return Ok(state.filtered[selected].value.clone());
+                        return Ok(state.filtered[selected].item.value.clone());
                     }
                 }
 
@@ -91,6 +144,10 @@ fn run_app<B: Backend, T: Clone>(terminal: &mut Terminal<B>, mut state: State<T>
                     bail!("User cancelled");
                 }
 
+                KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    state.sort_by_date = !state.sort_by_date;
+                }
+
                 KeyCode::Up => match state.list_state.selected() {
                     Some(selected) => {
                         if selected > 0 {
@@ -130,7 +187,7 @@ fn run_app<B: Backend, T: Clone>(terminal: &mut Terminal<B>, mut state: State<T>
 fn draw_ui<T: Clone>(f: &mut Frame, state: &mut State<T>) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Length(1), Constraint::Length(10)])
+        .constraints([Constraint::Length(1), Constraint::Min(0)])
         .split(f.area());
 
     // === Draw input line === //
@@ -156,8 +213,7 @@ fn draw_ui<T: Clone>(f: &mut Frame, state: &mut State<T>) {
     let results = state
         .filtered
         .iter()
-        .cloned()
-        .map(|item| ListItem::new(item.display))
+        .map(|filtered| ListItem::new(highlight_matches(&filtered.item.display, &filtered.matches)))
         .collect::<Vec<_>>();
 
     let results = List::new(results).highlight_style(Style::default().bg(Color::Black));
@@ -165,44 +221,128 @@ fn draw_ui<T: Clone>(f: &mut Frame, state: &mut State<T>) {
     f.render_stateful_widget(results, chunks[1], &mut state.list_state);
 }
 
-fn fuzzy_find_match<T: Clone>(query: &str, list: &[FuzzyFinderItem<T>]) -> Vec<FuzzyFinderItem<T>> {
+fn highlight_matches<'a>(display: &'a str, matches: &[Range<usize>]) -> Line<'a> {
+    let highlight = Style::default()
+        .fg(Color::Yellow)
+        .add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+
+    let mut spans = vec![];
+    let mut pos = 0;
+
+    for range in matches {
+        // Ranges are sorted, but may overlap
+        let start = range.start.max(pos);
+
+        if start >= range.end {
+            continue;
+        }
+
+        if start > pos {
+            spans.push(Span::raw(&display[pos..start]));
+        }
+
+        spans.push(Span::styled(&display[start..range.end], highlight));
+        pos = range.end;
+    }
+
+    if pos < display.len() {
+        spans.push(Span::raw(&display[pos..]));
+    }
+
+    Line::from(spans)
+}
+
+fn fuzzy_find_match<T: Clone>(
+    query: &str,
+    list: &[FuzzyFinderItem<T>],
+    ignore_case: bool,
+    limit: Option<usize>,
+    sort_by_date: bool,
+) -> Vec<FilteredItem<T>> {
+    let limit = limit.unwrap_or(usize::MAX);
+
     if query.is_empty() {
-        return list.to_vec();
+        let mut items = list.iter().collect::<Vec<_>>();
+
+        if sort_by_date {
+            items.sort_by_key(|item| std::cmp::Reverse(item.date));
+        }
+
+        return items
+            .into_iter()
+            .take(limit)
+            .cloned()
+            .map(|item| FilteredItem {
+                item,
+                matches: vec![],
+            })
+            .collect();
     }
 
     let mut scores = list
         .iter()
         .enumerate()
-        .map(|(i, item)| (i, compute_fuzzy_find_score(query, &item.display)))
-        .filter(|(_, score)| *score > 0)
+        .map(|(i, item)| {
+            (
+                i,
+                compute_fuzzy_find_score(query, &item.display, ignore_case),
+            )
+        })
+        .filter(|(_, (score, _))| *score > 0)
         .collect::<Vec<_>>();
 
-    scores.sort_by_key(|(_, score)| *score);
+    if sort_by_date {
+        scores.sort_by_key(|(i, (score, _))| (*score, list[*i].date));
+    } else {
+        scores.sort_by_key(|(_, (score, _))| *score);
+    }
 
+    // Only the displayed items are cloned
     scores
         .into_iter()
-        .map(|(i, _)| list.get(i).unwrap())
         .rev()
-        .cloned()
+        .take(limit)
+        .map(|(i, (_, matches))| FilteredItem {
+            item: list.get(i).unwrap().clone(),
+            matches,
+        })
         .collect()
 }
 
-fn compute_fuzzy_find_score(query: &str, subject: &str) -> usize {
-    query
+/// Compute the score of a subject for a query, alongside the (sorted) byte ranges matched in the subject
+pub fn compute_fuzzy_find_score(
+    query: &str,
+    subject: &str,
+    ignore_case: bool,
+) -> (usize, Vec<Range<usize>>) {
+    let case_sensitive = is_case_sensitive(query, ignore_case);
+
+    let mut matches = vec![];
+
+    let score = query
         .split_ascii_whitespace()
         .filter_map(|word| {
-            if subject.contains(word) {
-                Some(word.chars().count())
-            } else {
-                None
-            }
+            matches.push(find_match(subject, word, case_sensitive)?);
+            Some(word.chars().count())
         })
-        .sum()
+        .sum();
+
+    matches.sort_by_key(|range| range.start);
+
+    (score, matches)
+}
+
+struct FilteredItem<T: Clone> {
+    item: FuzzyFinderItem<T>,
+    matches: Vec<Range<usize>>,
 }
 
 struct State<T: Clone> {
     input_widget: Input,
     list: Vec<FuzzyFinderItem<T>>,
     list_state: ListState,
-    filtered: Vec<FuzzyFinderItem<T>>,
+    filtered: Vec<FilteredItem<T>>,
+    ignore_case: bool,
+    limit: Option<usize>,
+    sort_by_date: bool,
 }
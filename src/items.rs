@@ -1,52 +1,97 @@
 use std::{
+    borrow::Cow,
+    ffi::{OsStr, OsString},
+    fs::{self, File, FileTimes, Metadata},
+    io,
+    path::{Path, PathBuf},
     str,
     sync::LazyLock,
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
 
 static NAME_ID_SEPARATOR: &str = " ^";
 
-static DATE_REFERENTIAL: LazyLock<SystemTime> =
-    LazyLock::new(|| 
+static DATE_REFERENTIAL: LazyLock<SystemTime> = LazyLock::new(|| 
         // 2024 January 1st. 00:00:00 UTC
-        UNIX_EPOCH + Duration::from_secs(1704067200)
-    );
+        UNIX_EPOCH + Duration::from_secs(1704067200));
+
+/// Prefix of the IDs of items deleted before the date referential (e.g. with a skewed clock)
+///
+/// It isn't part of the base64 alphabet, so these IDs can't be confused with the other ones.
+static BEFORE_REFERENTIAL_ID_PREFIX: &str = "~";
 
 #[derive(Debug, Clone)]
 pub struct TrashItemInfos {
-    pub filename: String,
+    pub filename: OsString,
     pub datetime: SystemTime,
+    pub metadata: TrashItemMetadata,
 }
 
 impl TrashItemInfos {
-    pub fn new(filename: String, datetime: SystemTime) -> Self {
-        Self { filename, datetime }
+    pub fn new(filename: OsString, datetime: SystemTime) -> Self {
+        Self {
+            filename,
+            datetime,
+            metadata: TrashItemMetadata::default(),
+        }
     }
 
-    pub fn new_now(filename: String) -> Self {
+    pub fn new_now(filename: OsString) -> Self {
         Self::new(filename, SystemTime::now())
     }
 
+    /// Get the item's filename, with invalid UTF-8 sequences replaced
+    pub fn filename_lossy(&self) -> Cow<'_, str> {
+        self.filename.to_string_lossy()
+    }
+
+    /// Check if the item's filename contains invalid UTF-8 sequences
+    pub fn has_lossy_filename(&self) -> bool {
+        self.filename.to_str().is_none()
+    }
+
     pub fn compute_id(&self) -> String {
-        let id_bytes = self.datetime.duration_since(*DATE_REFERENTIAL).unwrap().as_nanos().to_be_bytes();
+        // Dates before the referential are encoded as the (positive) duration before it, with a prefix
+        let (prefix, nanos) = match self.datetime.duration_since(*DATE_REFERENTIAL) {
+            Ok(after) => ("", after.as_nanos()),
+            Err(err) => (BEFORE_REFERENTIAL_ID_PREFIX, err.duration().as_nanos()),
+        };
+
+        let id_bytes = nanos.to_be_bytes();
         let id_bytes = &id_bytes[id_bytes.iter().position(|b| *b != 0).unwrap_or(0)..];
 
-        URL_SAFE_NO_PAD.encode(id_bytes)
+        format!("{prefix}{}", URL_SAFE_NO_PAD.encode(id_bytes))
     }
 
-    pub fn trash_filename(&self) -> String {
-        format!("{}{NAME_ID_SEPARATOR}{}", self.filename, self.compute_id())
+    pub fn trash_filename(&self) -> OsString {
+        let mut trash_filename = self.filename.clone();
+        trash_filename.push(NAME_ID_SEPARATOR);
+        trash_filename.push(self.compute_id());
+        trash_filename
     }
 
-    pub fn decode(trash_filename: &str) -> Result<TrashItemInfos, TrashItemDecodingError> {
-        let circumflex_pos = trash_filename
+    pub fn decode(trash_filename: &OsStr) -> Result<TrashItemInfos, TrashItemDecodingError> {
+        // The ID suffix is always ASCII, so it's preserved by lossy conversion
+        let lossy = trash_filename.to_string_lossy();
+
+        // IDs are URL-safe base64 and can't contain the separator, so looking for its last occurrence
+        // keeps names containing it intact (e.g. an item literally named 'report ^AbC')
+        let circumflex_pos = lossy
             .rfind(NAME_ID_SEPARATOR)
             .ok_or(TrashItemDecodingError::InvalidFilenameFormat)?;
 
+        let id = &lossy[circumflex_pos + NAME_ID_SEPARATOR.len()..];
+
+        let (before_referential, id) = match id.strip_prefix(BEFORE_REFERENTIAL_ID_PREFIX) {
+            Some(id) => (true, id),
+            None => (false, id),
+        };
+
         let id = URL_SAFE_NO_PAD
-            .decode(&trash_filename[circumflex_pos + NAME_ID_SEPARATOR.len()..])
+            .decode(id)
             .map_err(|_| TrashItemDecodingError::BadlyEncodedId)?;
 
         if id.is_empty() || id.len() > 16 {
@@ -58,14 +103,233 @@ impl TrashItemInfos {
 
         let id = u128::from_be_bytes(int_bytes);
 
-        let datetime = *DATE_REFERENTIAL
-            + Duration::from_secs((id / 1_000_000_000) as u64)
-            + Duration::from_nanos((id % 1_000_000_000) as u64);
+        let offset = u64::try_from(id / 1_000_000_000)
+            .map(|secs| Duration::new(secs, (id % 1_000_000_000) as u32))
+            .map_err(|_| TrashItemDecodingError::DateOutOfRange)?;
 
-        Ok(Self::new(
-            trash_filename[0..circumflex_pos].to_owned(),
-            datetime,
-        ))
+        let datetime = if before_referential {
+            DATE_REFERENTIAL.checked_sub(offset)
+        } else {
+            DATE_REFERENTIAL.checked_add(offset)
+        }
+        .ok_or(TrashItemDecodingError::DateOutOfRange)?;
+
+        let filename = match trash_filename.to_str() {
+            Some(trash_filename) => OsString::from(&trash_filename[0..circumflex_pos]),
+            None => strip_ascii_suffix(trash_filename, lossy.len() - circumflex_pos),
+        };
+
+        Ok(Self::new(filename, datetime))
+    }
+}
+
+/// Remove an ASCII suffix of the provided length from a (possibly not UTF-8) string
+fn strip_ascii_suffix(string: &OsStr, suffix_len: usize) -> OsString {
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::ffi::OsStrExt;
+
+        let bytes = string.as_bytes();
+        OsStr::from_bytes(&bytes[..bytes.len() - suffix_len]).to_owned()
+    }
+
+    #[cfg(target_family = "windows")]
+    {
+        use std::os::windows::ffi::{OsStrExt, OsStringExt};
+
+        let wide = string.encode_wide().collect::<Vec<_>>();
+        OsString::from_wide(&wide[..wide.len() - suffix_len])
+    }
+}
+
+/// Version of the metadata files' format written by this version of Trasher
+///
+/// Items without a metadata file are considered to use version 0, where all
+/// informations are decoded from the item's filename.
+pub const METADATA_VERSION: u32 = 1;
+
+/// Additional informations about a trash item that can't be stored in its filename
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TrashItemMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<ItemOwner>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attributes: Option<ItemAttributes>,
+
+    /// Name of the machine the item was trashed from, to tell items apart in trashes synced across machines
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+
+    /// Absolute path the item was located at before being trashed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_path: Option<PathBuf>,
+}
+
+impl TrashItemMetadata {
+    /// Get the name of the current machine, if it can be determined
+    pub fn current_hostname() -> Option<String> {
+        hostname::get().ok()?.into_string().ok()
+    }
+
+    /// Encode the metadata as the content of a metadata file
+    pub fn encode(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(&MetadataFile {
+            version: METADATA_VERSION,
+            metadata: self.clone(),
+        })
+    }
+
+    /// Decode the content of a metadata file, returning the version it was written with
+    ///
+    /// Fields unknown to this version of Trasher (e.g. written by a newer one) are ignored.
+    pub fn decode(content: &str) -> Result<(u32, Self), serde_json::Error> {
+        let MetadataFile { version, metadata } = serde_json::from_str(content)?;
+        Ok((version, metadata))
+    }
+}
+
+/// Content of a metadata file
+#[derive(Serialize, Deserialize)]
+struct MetadataFile {
+    // Metadata files written before versioning was introduced use the same format as version 1
+    #[serde(default = "first_metadata_version")]
+    version: u32,
+
+    #[serde(flatten)]
+    metadata: TrashItemMetadata,
+}
+
+fn first_metadata_version() -> u32 {
+    1
+}
+
+/// Attributes of an item at the time it was moved to the trash, reapplied when it's restored
+///
+/// They may otherwise be lost when the item is moved across filesystems.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemAttributes {
+    /// Unix permissions mode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+
+    #[serde(default)]
+    pub readonly: bool,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<SystemTime>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accessed: Option<SystemTime>,
+}
+
+impl ItemAttributes {
+    /// Get the attributes of an item
+    ///
+    /// Symbolic links don't have attributes of their own, so `None` is returned for them.
+    pub fn of(mt: &Metadata) -> Option<Self> {
+        if mt.is_symlink() {
+            return None;
+        }
+
+        #[cfg(target_family = "unix")]
+        let mode = {
+            use std::os::unix::fs::PermissionsExt;
+            Some(mt.permissions().mode())
+        };
+
+        #[cfg(not(target_family = "unix"))]
+        let mode = None;
+
+        Some(Self {
+            mode,
+            readonly: mt.permissions().readonly(),
+            modified: mt.modified().ok(),
+            accessed: mt.accessed().ok(),
+        })
+    }
+
+    /// Apply the attributes to an item
+    pub fn apply(&self, path: &Path) -> io::Result<()> {
+        let mut times = FileTimes::new();
+
+        if let Some(modified) = self.modified {
+            times = times.set_modified(modified);
+        }
+
+        if let Some(accessed) = self.accessed {
+            times = times.set_accessed(accessed);
+        }
+
+        // Times must be set first, as the permissions may prevent from opening the item
+        // NOTE: Directories can't be opened this way on Windows
+        if cfg!(target_family = "unix") || !fs::metadata(path)?.is_dir() {
+            File::open(path)?.set_times(times)?;
+        }
+
+        let mut permissions = fs::metadata(path)?.permissions();
+
+        #[cfg(target_family = "unix")]
+        if let Some(mode) = self.mode {
+            use std::os::unix::fs::PermissionsExt;
+            permissions.set_mode(mode);
+        }
+
+        #[cfg(not(target_family = "unix"))]
+        permissions.set_readonly(self.readonly);
+
+        fs::set_permissions(path, permissions)
+    }
+}
+
+/// User who moved an item to the trash
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemOwner {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+}
+
+impl ItemOwner {
+    /// Get the current user
+    pub fn current() -> Option<Self> {
+        #[cfg(target_family = "unix")]
+        {
+            let uid = nix::unistd::getuid();
+
+            let username = nix::unistd::User::from_uid(uid)
+                .ok()
+                .flatten()
+                .map(|user| user.name);
+
+            Some(Self {
+                uid: Some(uid.as_raw()),
+                username,
+            })
+        }
+
+        #[cfg(target_family = "windows")]
+        {
+            std::env::var("USERNAME").ok().map(|username| Self {
+                uid: None,
+                username: Some(username),
+            })
+        }
+    }
+}
+
+impl std::fmt::Display for ItemOwner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.username, self.uid) {
+            (Some(username), _) => write!(f, "{username}"),
+            (None, Some(uid)) => write!(f, "{uid}"),
+            (None, None) => write!(f, "<unknown>"),
+        }
     }
 }
 
@@ -74,4 +338,149 @@ pub enum TrashItemDecodingError {
     InvalidFilenameFormat,
     BadlyEncodedId,
     InvalidIdLength,
+    DateOutOfRange,
+}
+
+impl std::fmt::Display for TrashItemDecodingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidFilenameFormat => write!(
+                f,
+                "filename doesn't contain the '{NAME_ID_SEPARATOR}' separator followed by an ID"
+            ),
+            Self::BadlyEncodedId => write!(f, "ID is not valid URL-safe base64"),
+            Self::InvalidIdLength => write!(f, "ID must encode between 1 and 16 bytes"),
+            Self::DateOutOfRange => write!(f, "ID encodes a date that can't be represented"),
+        }
+    }
+}
+
+impl std::error::Error for TrashItemDecodingError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(datetime: SystemTime) -> TrashItemInfos {
+        let item = TrashItemInfos::new(OsString::from("report.txt"), datetime);
+        let decoded = TrashItemInfos::decode(&item.trash_filename()).unwrap();
+
+        assert_eq!(decoded.filename, item.filename);
+        assert_eq!(decoded.datetime, datetime);
+        assert_eq!(decoded.compute_id(), item.compute_id());
+
+        decoded
+    }
+
+    #[test]
+    fn id_round_trip_after_referential() {
+        let item = round_trip(*DATE_REFERENTIAL + Duration::new(15_681_600, 123_456_789));
+        assert!(!item.compute_id().starts_with(BEFORE_REFERENTIAL_ID_PREFIX));
+
+        round_trip(*DATE_REFERENTIAL + Duration::from_nanos(1));
+        round_trip(SystemTime::now());
+    }
+
+    #[test]
+    fn id_round_trip_before_referential() {
+        let item = round_trip(*DATE_REFERENTIAL - Duration::from_secs(4_067_200));
+        assert!(item.compute_id().starts_with(BEFORE_REFERENTIAL_ID_PREFIX));
+
+        round_trip(*DATE_REFERENTIAL - Duration::from_nanos(1));
+        round_trip(UNIX_EPOCH);
+    }
+
+    #[test]
+    fn decode_existing_names() {
+        // Name written by versions of Trasher that didn't support dates before the referential
+        let item = TrashItemInfos::decode(OsStr::new("report.txt ^N7ZVBYKAAA")).unwrap();
+
+        assert_eq!(item.filename, "report.txt");
+        assert_eq!(
+            item.datetime,
+            UNIX_EPOCH + Duration::from_secs(1_719_748_800)
+        );
+        assert_eq!(item.compute_id(), "N7ZVBYKAAA");
+
+        let item = TrashItemInfos::decode(OsStr::new("report.txt ^~DnMYyzsAAA")).unwrap();
+        assert_eq!(
+            item.datetime,
+            UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+        );
+    }
+
+    #[test]
+    fn decode_invalid_names() {
+        assert!(TrashItemInfos::decode(OsStr::new("report.txt")).is_err());
+        assert!(TrashItemInfos::decode(OsStr::new("report.txt ^")).is_err());
+        assert!(TrashItemInfos::decode(OsStr::new("report.txt ^not base64!")).is_err());
+        assert!(
+            TrashItemInfos::decode(OsStr::new("report.txt ^AAAAAAAAAAAAAAAAAAAAAAAA")).is_err()
+        );
+    }
+
+    #[test]
+    fn decode_name_containing_separator() {
+        let item = TrashItemInfos::decode(OsStr::new("report ^AbC ^N7ZVBYKAAA")).unwrap();
+
+        assert_eq!(item.filename, "report ^AbC");
+        assert_eq!(item.compute_id(), "N7ZVBYKAAA");
+    }
+
+    #[test]
+    fn decode_metadata_without_version() {
+        let content = r#"{ "owner": { "uid": 1000, "username": "user" }, "tags": ["work"] }"#;
+
+        let (version, metadata) = TrashItemMetadata::decode(content).unwrap();
+
+        assert_eq!(version, 1);
+        assert_eq!(metadata.tags, ["work"]);
+
+        let owner = metadata.owner.unwrap();
+        assert_eq!(owner.uid, Some(1000));
+        assert_eq!(owner.username.as_deref(), Some("user"));
+    }
+
+    #[test]
+    fn metadata_round_trip() {
+        let metadata = TrashItemMetadata {
+            owner: None,
+            tags: vec!["work".to_owned(), "drafts".to_owned()],
+            attributes: Some(ItemAttributes {
+                mode: Some(0o644),
+                readonly: false,
+                modified: Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+                accessed: None,
+            }),
+            hostname: Some("workstation".to_owned()),
+            original_path: Some(PathBuf::from("/home/user/report.txt")),
+        };
+
+        let content = metadata.encode().unwrap();
+        assert!(content.contains(&format!("\"version\": {METADATA_VERSION}")));
+
+        let (version, decoded) = TrashItemMetadata::decode(&content).unwrap();
+
+        assert_eq!(version, METADATA_VERSION);
+        assert_eq!(decoded.tags, metadata.tags);
+        assert_eq!(decoded.hostname, metadata.hostname);
+        assert_eq!(decoded.original_path, metadata.original_path);
+
+        let attributes = decoded.attributes.unwrap();
+        assert_eq!(attributes.mode, Some(0o644));
+        assert_eq!(
+            attributes.modified,
+            Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+        );
+    }
+
+    #[test]
+    fn decode_metadata_with_unknown_fields() {
+        let content = format!(r#"{{ "version": {METADATA_VERSION}, "future_field": 1 }}"#);
+
+        let (version, metadata) = TrashItemMetadata::decode(&content).unwrap();
+
+        assert_eq!(version, METADATA_VERSION);
+        assert!(metadata.tags.is_empty());
+    }
 }
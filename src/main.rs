@@ -7,10 +7,17 @@
 
 mod actions;
 mod args;
+mod dates;
 mod display;
+mod errors;
 mod fsutils;
 mod fuzzy;
 mod items;
+mod pending;
+mod policy;
+mod server;
+mod signals;
+mod system_trash;
 
 use std::{
     process::ExitCode,
@@ -23,40 +30,64 @@ use clap::Parser;
 
 fn main() -> ExitCode {
     match inner_main() {
-        Ok(()) => ExitCode::SUCCESS,
+        Ok(code) => code,
         Err(err) => {
             error!("ERROR: {err:?}");
-            ExitCode::FAILURE
+            errors::exit_code_for(&err)
         }
     }
 }
 
-fn inner_main() -> Result<()> {
+fn inner_main() -> Result<ExitCode> {
     let Opts {
         verbose,
+        no_progress,
         action,
-        config,
+        mut config,
     } = Opts::parse();
 
     if verbose {
         PRINT_DEBUG_MESSAGES.store(true, Ordering::SeqCst);
     }
 
+    if no_progress {
+        HIDE_PROGRESS.store(true, Ordering::SeqCst);
+    }
+
     match action {
-        Action::List(args) => actions::list(args, &config)?,
+        Action::List(args) => return actions::list(args, &config),
         Action::Remove(args) => actions::remove(args, &config)?,
         Action::Drop(args) => actions::drop(args, &config)?,
         Action::PathOf(args) => actions::path_of(args, &config)?,
+        Action::Info(args) => actions::info(args, &config)?,
         Action::Restore(args) => actions::restore(args, &config)?,
-        Action::Empty => actions::empty(&config)?,
-        Action::TrashPath => actions::trash_path(&config)?,
+        Action::Export(args) => actions::export(args, &config)?,
+        Action::Empty(args) => actions::empty(args, &config)?,
+        Action::Purge(args) => actions::purge(args, &config)?,
+        Action::Doctor(args) => actions::doctor(args, &config)?,
+        Action::Compact(args) => actions::compact(args, &config)?,
+        Action::TrashPath(args) => actions::trash_path(args, &config)?,
+        Action::Find(args) => actions::find(args, &config)?,
+        Action::Size(args) => actions::size(args, &config)?,
+        Action::Decode(args) => actions::decode(args)?,
+        Action::Commit(args) => actions::commit(args, &config)?,
+        Action::Abort(args) => actions::abort(args)?,
+        Action::Serve(args) => {
+            // Clients can't answer prompts, so operations requiring a confirmation fail instead
+            config.assume_yes = false;
+            config.non_interactive = true;
+
+            server::serve(&args.socket, &config)?
+        }
     }
 
-    Ok(())
+    Ok(ExitCode::SUCCESS)
 }
 
 pub static PRINT_DEBUG_MESSAGES: AtomicBool = AtomicBool::new(false);
 
+pub static HIDE_PROGRESS: AtomicBool = AtomicBool::new(false);
+
 #[macro_export]
 macro_rules! debug {
     ($message: expr$(,$params: expr)*) => {{
@@ -0,0 +1,136 @@
+//! Store of the deletions delayed with `rm --delay`, waiting to be committed or aborted
+
+use std::{
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// Prefix of the names items are renamed to while their deletion is pending
+pub const PENDING_ITEM_PREFIX: &str = ".#PENDING ";
+
+/// Name of the pending operations store, inside the local data directory
+const PENDING_STORE_FILENAME: &str = "pending.json";
+
+/// Deletion waiting for its delay to expire
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingDeletion {
+    /// Path the item was located at
+    pub original_path: PathBuf,
+
+    /// Path the item was renamed to while waiting
+    pub pending_path: PathBuf,
+
+    /// Time after which the item can be moved to the trash, in seconds since the Unix epoch
+    pub deadline: u64,
+
+    /// Tags to attach to the item once it's moved to the trash
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl PendingDeletion {
+    pub fn new(
+        original_path: PathBuf,
+        pending_path: PathBuf,
+        delay: Duration,
+        tags: Vec<String>,
+    ) -> Self {
+        let deadline = (SystemTime::now() + delay)
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+
+        Self {
+            original_path,
+            pending_path,
+            deadline,
+            tags,
+        }
+    }
+
+    /// Check if the deletion's delay has expired
+    pub fn is_expired(&self) -> bool {
+        SystemTime::now() >= self.deadline_time()
+    }
+
+    /// Get the deletion's deadline
+    pub fn deadline_time(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.deadline)
+    }
+}
+
+/// Compute the path an item is renamed to while its deletion is pending
+pub fn pending_path_for(path: &Path) -> Result<PathBuf> {
+    let filename = path
+        .file_name()
+        .context("Specified item path has no file name")?;
+
+    let mut pending_name = OsString::from(PENDING_ITEM_PREFIX);
+    pending_name.push(filename);
+
+    Ok(path.with_file_name(pending_name))
+}
+
+/// Get the path of the pending operations store
+fn pending_store_path() -> Result<PathBuf> {
+    let data_dir =
+        dirs::data_local_dir().context("Failed to determine the local data directory")?;
+
+    Ok(data_dir.join("trasher").join(PENDING_STORE_FILENAME))
+}
+
+/// Load the pending deletions
+pub fn load_pending_deletions() -> Result<Vec<PendingDeletion>> {
+    let path = pending_store_path()?;
+
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+
+    let content = fs::read_to_string(&path).with_context(|| {
+        format!(
+            "Failed to read pending operations file at path: {}",
+            path.display()
+        )
+    })?;
+
+    serde_json::from_str(&content).context("Failed to parse pending operations file")
+}
+
+/// Save the pending deletions, replacing the existing ones
+pub fn save_pending_deletions(deletions: &[PendingDeletion]) -> Result<()> {
+    let path = pending_store_path()?;
+
+    if deletions.is_empty() {
+        if path.exists() {
+            fs::remove_file(&path).with_context(|| {
+                format!(
+                    "Failed to remove pending operations file at path: {}",
+                    path.display()
+                )
+            })?;
+        }
+
+        return Ok(());
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+
+    let content = serde_json::to_string_pretty(deletions)
+        .context("Failed to serialize pending operations")?;
+
+    fs::write(&path, content).with_context(|| {
+        format!(
+            "Failed to write pending operations file at path: {}",
+            path.display()
+        )
+    })
+}
@@ -0,0 +1,101 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+
+use crate::{dates::parse_duration, fsutils::parse_size};
+
+/// Name of the retention policy file, inside the configuration directory
+pub const RETENTION_POLICY_FILENAME: &str = "retention.conf";
+
+/// Retention rule applying to the trash directories located under a given path
+#[derive(Debug)]
+pub struct RetentionRule {
+    pub prefix: PathBuf,
+    pub max_age: Option<Duration>,
+    pub max_size: Option<u64>,
+}
+
+/// Get the default path of the retention policy file
+pub fn default_retention_policy_path() -> Result<PathBuf> {
+    let config_dir =
+        dirs::config_dir().context("Failed to determine the configuration directory")?;
+
+    Ok(config_dir.join("trasher").join(RETENTION_POLICY_FILENAME))
+}
+
+/// Load a retention policy file
+///
+/// Each non-empty line that doesn't start with a `#` describes a rule, made of a path
+/// followed by a `max-age=<duration>` and / or `max-size=<size>` settings:
+///
+/// ```text
+/// /            max-age=30d
+/// /mnt/scratch max-age=1d max-size=10GiB
+/// ```
+pub fn load_retention_policy(path: &Path) -> Result<Vec<RetentionRule>> {
+    let content = fs::read_to_string(path).with_context(|| {
+        format!(
+            "Failed to read retention policy file at path: {}",
+            path.display()
+        )
+    })?;
+
+    content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| {
+            parse_retention_rule(line)
+                .with_context(|| format!("Invalid retention rule at line {}", i + 1))
+        })
+        .collect()
+}
+
+fn parse_retention_rule(line: &str) -> Result<RetentionRule> {
+    let mut parts = line.split_whitespace();
+
+    let prefix = PathBuf::from(parts.next().context("Missing path")?);
+
+    // Canonicalize the path when possible so it can be compared to trash directories
+    let prefix = fs::canonicalize(&prefix).unwrap_or(prefix);
+
+    let mut rule = RetentionRule {
+        prefix,
+        max_age: None,
+        max_size: None,
+    };
+
+    for setting in parts {
+        let (key, value) = setting
+            .split_once('=')
+            .with_context(|| format!("Expected a 'key=value' setting, found: {setting}"))?;
+
+        match key {
+            "max-age" => rule.max_age = Some(parse_duration(value).map_err(anyhow::Error::msg)?),
+            "max-size" => rule.max_size = Some(parse_size(value).map_err(anyhow::Error::msg)?),
+            _ => bail!("Unknown setting: {key}"),
+        }
+    }
+
+    if rule.max_age.is_none() && rule.max_size.is_none() {
+        bail!("Rule doesn't have any 'max-age' or 'max-size' setting");
+    }
+
+    Ok(rule)
+}
+
+/// Find the most specialized rule applying to a trash directory
+pub fn find_retention_rule<'a>(
+    rules: &'a [RetentionRule],
+    trash_dir: &Path,
+) -> Option<&'a RetentionRule> {
+    rules
+        .iter()
+        .filter(|rule| trash_dir.starts_with(&rule.prefix))
+        .max_by_key(|rule| rule.prefix.components().count())
+}
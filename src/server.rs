@@ -0,0 +1,306 @@
+//! Serve trash operations over a Unix socket, for file managers and editor plugins
+//!
+//! Each request is a JSON object on its own line, answered with a JSON object on its own line:
+//!
+//! ```text
+//! {"op":"trash","paths":["/home/user/file.txt"]}
+//! {"op":"list","name":"file"}
+//! {"op":"restore","filename":"file.txt","id":"AThGtKA41lQ","to":"/home/user"}
+//! ```
+
+// Requests can only be received on Unix systems
+#![cfg_attr(not(unix), allow(dead_code))]
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    actions,
+    args::{Config, MoveToTrash, RestoreItem},
+    errors::TrasherError,
+    fsutils::{
+        clear_device_ids_cache, expect_trash_item, list_all_trash_items, FoundTrashItems,
+        ItemDescription,
+    },
+    fuzzy::{find_match, is_case_sensitive},
+};
+
+/// Request sent by a client
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum Request {
+    Trash {
+        paths: Vec<PathBuf>,
+        #[serde(default)]
+        permanently: bool,
+    },
+    List {
+        name: Option<String>,
+    },
+    Restore {
+        filename: Option<String>,
+        id: Option<String>,
+        to: PathBuf,
+    },
+}
+
+/// Response sent back to the client
+#[derive(Serialize)]
+struct Response {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    items: Option<Vec<ItemDescription>>,
+}
+
+/// Listen on a Unix socket and handle requests until the process is stopped
+#[cfg(unix)]
+pub fn serve(socket_path: &Path, config: &Config) -> Result<()> {
+    use std::{
+        fs,
+        io::{BufRead, BufReader, Write},
+        os::unix::{fs::FileTypeExt, net::UnixListener},
+    };
+
+    use anyhow::Context;
+
+    use crate::{debug, info, warn};
+
+    // Remove the socket left behind by a previous server
+    if let Ok(mt) = fs::symlink_metadata(socket_path) {
+        if !mt.file_type().is_socket() {
+            bail!(
+                "Path '{}' already exists and is not a socket",
+                socket_path.display()
+            );
+        }
+
+        fs::remove_file(socket_path).with_context(|| {
+            format!(
+                "Failed to remove previous socket at path: {}",
+                socket_path.display()
+            )
+        })?;
+    }
+
+    let listener = UnixListener::bind(socket_path)
+        .with_context(|| format!("Failed to listen on socket: {}", socket_path.display()))?;
+
+    info!("Listening on socket: {}", socket_path.display());
+
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                warn!("Failed to accept connection: {err}");
+                continue;
+            }
+        };
+
+        debug!("Accepted a new connection");
+
+        let reader = match stream.try_clone() {
+            Ok(reader) => BufReader::new(reader),
+            Err(err) => {
+                warn!("Failed to read from connection: {err}");
+                continue;
+            }
+        };
+
+        for line in reader.lines() {
+            let Ok(line) = line else {
+                break;
+            };
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let response = match serde_json::from_str::<Request>(&line) {
+                Ok(request) => handle_request(request, config),
+                Err(err) => Response::error(format!("Invalid request: {err}")),
+            };
+
+            let mut response =
+                serde_json::to_string(&response).context("Failed to serialize response")?;
+
+            response.push('\n');
+
+            if let Err(err) = stream.write_all(response.as_bytes()) {
+                warn!("Failed to send response: {err}");
+                break;
+            }
+        }
+
+        debug!("Connection closed");
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn serve(_: &Path, _: &Config) -> Result<()> {
+    bail!("Serving over a socket is only supported on Unix systems")
+}
+
+fn handle_request(request: Request, config: &Config) -> Response {
+    // Filesystems may have been mounted or unmounted since the previous request
+    clear_device_ids_cache();
+
+    let result = match request {
+        Request::Trash { paths, permanently } => {
+            trash_items(paths, permanently, config).map(|()| None)
+        }
+
+        Request::List { name } => list_items(name.as_deref(), config).map(Some),
+
+        Request::Restore { filename, id, to } => {
+            restore_item(filename, id, to, config).map(|()| None)
+        }
+    };
+
+    match result {
+        Ok(items) => Response {
+            ok: true,
+            error: None,
+            items,
+        },
+        Err(err) => Response::error(format!("{err:#}")),
+    }
+}
+
+/// Ensure a path provided in a request is absolute, as relative ones would be resolved from the server's working directory
+fn ensure_absolute(path: &Path) -> Result<()> {
+    if !path.is_absolute() {
+        bail!(
+            "Paths in requests must be absolute, got: {}",
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
+fn trash_items(paths: Vec<PathBuf>, permanently: bool, config: &Config) -> Result<()> {
+    for path in &paths {
+        ensure_absolute(path)?;
+    }
+
+    actions::remove(
+        MoveToTrash {
+            paths,
+            permanently,
+            quiet: true,
+            ..Default::default()
+        },
+        config,
+    )
+}
+
+fn list_items(name: Option<&str>, config: &Config) -> Result<Vec<ItemDescription>> {
+    let mut items = list_all_trash_items(config)?;
+
+    if let Some(name) = name {
+        let case_sensitive = is_case_sensitive(name, config.ignore_case);
+
+        items.retain(|trashed| {
+            find_match(&trashed.data.filename_lossy(), name, case_sensitive).is_some()
+        });
+    }
+
+    Ok(items.iter().map(ItemDescription::new).collect())
+}
+
+fn restore_item(
+    filename: Option<String>,
+    id: Option<String>,
+    to: PathBuf,
+    config: &Config,
+) -> Result<()> {
+    ensure_absolute(&to)?;
+
+    // Resolve the item beforehand, as the interactive picker can't be used here
+    let item = match expect_trash_item(filename.as_deref(), id.as_deref(), &[], config)? {
+        FoundTrashItems::Single(item) => *item,
+        FoundTrashItems::Multi(candidates) => {
+            bail!(TrasherError::AmbiguousMatch(candidates))
+        }
+    };
+
+    actions::restore(
+        RestoreItem {
+            filename: Some(item.data.filename_lossy().into_owned()),
+            to: Some(to),
+            id: Some(item.data.compute_id()),
+            system: false,
+            dry_run: false,
+            all_matching: false,
+            merge: false,
+            limit: None,
+            older_than: None,
+            newer_than: None,
+            json: false,
+            force: false,
+        },
+        config,
+    )
+}
+
+impl Response {
+    fn error(message: String) -> Self {
+        Self {
+            ok: false,
+            error: Some(message),
+            items: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_paths_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::for_tests(&dir.path().join("trash"), &[]);
+
+        let response = handle_request(
+            Request::Trash {
+                paths: vec![PathBuf::from("report.txt")],
+                permanently: false,
+            },
+            &config,
+        );
+
+        assert!(!response.ok);
+        assert!(response.error.unwrap().contains("must be absolute"));
+
+        let response = handle_request(
+            Request::Restore {
+                filename: Some("report.txt".to_owned()),
+                id: None,
+                to: PathBuf::from("."),
+            },
+            &config,
+        );
+
+        assert!(!response.ok);
+        assert!(response.error.unwrap().contains("must be absolute"));
+    }
+
+    #[test]
+    fn confirmations_fail_instead_of_prompting() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut config = Config::for_tests(&dir.path().join("trash"), &[]);
+        config.non_interactive = true;
+
+        // Nothing must be read from stdin, as it isn't the client's
+        let err = crate::display::confirm("Are you sure", &config).unwrap_err();
+        assert!(err.to_string().contains("Are you sure"));
+    }
+}
@@ -0,0 +1,151 @@
+//! Handling of signals: graceful stops of long-running commands, and removal of partial transfers when
+//! Trasher is stopped by a signal (e.g. Ctrl+C or a process supervisor)
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::{atomic::AtomicBool, Arc, Mutex, PoisonError},
+};
+
+use signal_hook::consts::{SIGINT, SIGTERM};
+
+use crate::args::Config;
+
+/// Path of the transfer currently in progress, removed if the process is stopped by a signal
+static PARTIAL_TRANSFER: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Transfer whose target is removed if the process receives SIGINT or SIGTERM, until it's marked as complete
+///
+/// The transfer must be marked as complete (with [`Self::complete`]) before the source is removed,
+/// so a complete copy is never removed.
+pub struct PartialTransferGuard {
+    tracked: bool,
+}
+
+impl PartialTransferGuard {
+    pub fn track(path: &Path, config: &Config) -> Self {
+        // Partial transfers are kept with '--no-cleanup', so the signals' default behaviour is kept too
+        if config.no_cleanup {
+            return Self { tracked: false };
+        }
+
+        install_handler();
+
+        *lock_partial_transfer() = Some(path.to_owned());
+
+        Self { tracked: true }
+    }
+
+    /// Stop removing the transfer's target if the process is stopped
+    pub fn complete(self) {}
+}
+
+impl Drop for PartialTransferGuard {
+    fn drop(&mut self) {
+        if self.tracked {
+            *lock_partial_transfer() = None;
+        }
+    }
+}
+
+fn lock_partial_transfer() -> std::sync::MutexGuard<'static, Option<PathBuf>> {
+    PARTIAL_TRANSFER
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Set the provided flag when the process receives SIGINT or SIGTERM, instead of stopping it
+///
+/// Used by long-running commands to stop gracefully between two steps.
+pub fn register_stop_flag(flag: &Arc<AtomicBool>) -> io::Result<()> {
+    for signal in [SIGINT, SIGTERM] {
+        signal_hook::flag::register(signal, Arc::clone(flag))?;
+    }
+
+    Ok(())
+}
+
+/// Handle SIGINT and SIGTERM in a dedicated thread, which removes the partial transfer (if any) before exiting
+///
+/// The handler stays installed until the process exits, as the signals' default behaviour can't be restored.
+#[cfg(unix)]
+fn install_handler() {
+    use std::{io::ErrorKind, sync::Once, thread, time::Duration};
+
+    use signal_hook::iterator::Signals;
+
+    use crate::{error, fsutils::remove_item, warn};
+
+    static INSTALL: Once = Once::new();
+
+    INSTALL.call_once(|| {
+        let mut signals = match Signals::new([SIGINT, SIGTERM]) {
+            Ok(signals) => signals,
+            Err(err) => {
+                warn!("Failed to register the interruption handler: {err}");
+                return;
+            }
+        };
+
+        thread::spawn(move || {
+            let Some(signal) = signals.forever().next() else {
+                return;
+            };
+
+            // The lock is kept until the process exits, so the transfer can't be marked as complete
+            // (and its source removed) in the meantime
+            let partial_transfer = lock_partial_transfer();
+
+            if let Some(path) = partial_transfer.as_ref() {
+                warn!(
+                    "\nInterrupted, removing partially transferred item '{}'...",
+                    path.display()
+                );
+
+                // The copy is still running in the main thread, so a directory may get new entries while it's removed
+                for attempt in 1..=3 {
+                    match remove_item(path) {
+                        Ok(()) => break,
+                        Err(err) if err.kind() == ErrorKind::NotFound => break,
+                        Err(err) if attempt == 3 => error!(
+                            "Failed to remove partially transferred item '{}': {err}",
+                            path.display()
+                        ),
+                        Err(_) => thread::sleep(Duration::from_millis(100)),
+                    }
+                }
+            }
+
+            std::process::exit(128 + signal);
+        });
+    });
+}
+
+/// Signals are only handled on Unix systems
+#[cfg(not(unix))]
+fn install_handler() {}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn transfers_are_not_tracked_without_cleanup() {
+        let path = Path::new("/tmp/transfer");
+
+        let config = Config::for_tests(Path::new("/tmp/trash"), &["--no-cleanup"]);
+        let transfer = PartialTransferGuard::track(path, &config);
+
+        assert!(lock_partial_transfer().is_none());
+        transfer.complete();
+
+        let config = Config::for_tests(Path::new("/tmp/trash"), &[]);
+        let transfer = PartialTransferGuard::track(path, &config);
+
+        assert_eq!(lock_partial_transfer().as_deref(), Some(path));
+        transfer.complete();
+        assert!(lock_partial_transfer().is_none());
+    }
+}
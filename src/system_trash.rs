@@ -0,0 +1,155 @@
+//! Integration with the operating system's own trash (Windows' Recycle Bin, macOS' Trash)
+
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+/// Move items to the system trash
+///
+/// On macOS, items are moved through Finder, which records their original location
+/// so they can be restored using its "Put Back" feature.
+#[cfg(any(target_family = "windows", target_os = "macos"))]
+pub fn move_to_system_trash(paths: &[PathBuf]) -> Result<()> {
+    use anyhow::Context;
+
+    trash::delete_all(paths).context("Failed to move items to the system trash")
+}
+
+/// List items in the system trash
+#[cfg(target_family = "windows")]
+pub fn list_system_trash(name: Option<&str>) -> Result<()> {
+    use anyhow::Context;
+    use comfy_table::{presets::UTF8_FULL_CONDENSED, ContentArrangement, Table};
+
+    use crate::{fsutils::format_date, info};
+
+    let mut items = trash::os_limited::list().context("Failed to list the Recycle Bin's items")?;
+
+    if let Some(name) = name {
+        items.retain(|item| item.name.to_string_lossy().contains(name));
+    }
+
+    if items.is_empty() {
+        info!("No item found in the Recycle Bin.");
+        return Ok(());
+    }
+
+    items.sort_by_key(|item| item.time_deleted);
+
+    let mut table = Table::new();
+
+    table
+        .load_preset(UTF8_FULL_CONDENSED)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(vec!["Filename", "Original location", "Deleted on"]);
+
+    for item in &items {
+        let deleted_on = u64::try_from(item.time_deleted)
+            .map(|secs| format_date(std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs)))
+            .unwrap_or_else(|_| "<Unknown>".to_owned());
+
+        table.add_row(vec![
+            item.name.to_string_lossy().into_owned(),
+            item.original_parent.to_string_lossy().into_owned(),
+            deleted_on,
+        ]);
+    }
+
+    println!("{table}");
+
+    Ok(())
+}
+
+/// Restore an item from the system trash to its original location
+#[cfg(target_family = "windows")]
+pub fn restore_from_system_trash(name: &str) -> Result<()> {
+    use anyhow::{bail, Context};
+
+    use crate::errors::TrasherError;
+
+    let mut candidates = trash::os_limited::list()
+        .context("Failed to list the Recycle Bin's items")?
+        .into_iter()
+        .filter(|item| item.name.to_string_lossy() == name)
+        .collect::<Vec<_>>();
+
+    let item = match candidates.len() {
+        0 => bail!(TrasherError::ItemNotFound),
+        1 => candidates.remove(0),
+        _ => bail!(
+            "Multiple items with this filename were found in the Recycle Bin:\n\n{}",
+            candidates
+                .iter()
+                .map(|item| format!("* {}", item.original_path().display()))
+                .collect::<Vec<_>>()
+                .join("\n")
+        ),
+    };
+
+    trash::os_limited::restore_all([item]).context("Failed to restore item from the Recycle Bin")
+}
+
+/// List items in the system trash
+#[cfg(target_os = "macos")]
+pub fn list_system_trash(name: Option<&str>) -> Result<()> {
+    use std::fs;
+
+    use anyhow::Context;
+
+    use crate::info;
+
+    let trash_dir = dirs::home_dir()
+        .context("Failed to determine path to user's home directory")?
+        .join(".Trash");
+
+    let mut names = fs::read_dir(&trash_dir)
+        .with_context(|| format!("Failed to read trash directory: {}", trash_dir.display()))?
+        .map(|entry| entry.map(|entry| entry.file_name().to_string_lossy().into_owned()))
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to read trash directory entry")?;
+
+    names.retain(|item_name| {
+        !item_name.starts_with('.') && name.is_none_or(|name| item_name.contains(name))
+    });
+
+    if names.is_empty() {
+        info!("No item found in the Trash.");
+        return Ok(());
+    }
+
+    names.sort();
+
+    for name in names {
+        println!("{name}");
+    }
+
+    Ok(())
+}
+
+/// Restore an item from the system trash
+#[cfg(target_os = "macos")]
+pub fn restore_from_system_trash(_: &str) -> Result<()> {
+    anyhow::bail!(
+        "Items can't be restored from the macOS Trash by Trasher, use Finder's 'Put Back' feature instead"
+    )
+}
+
+#[cfg(not(any(target_family = "windows", target_os = "macos")))]
+pub fn move_to_system_trash(_: &[PathBuf]) -> Result<()> {
+    unsupported()
+}
+
+#[cfg(not(any(target_family = "windows", target_os = "macos")))]
+pub fn list_system_trash(_: Option<&str>) -> Result<()> {
+    unsupported()
+}
+
+#[cfg(not(any(target_family = "windows", target_os = "macos")))]
+pub fn restore_from_system_trash(_: &str) -> Result<()> {
+    unsupported()
+}
+
+#[cfg(not(any(target_family = "windows", target_os = "macos")))]
+fn unsupported() -> Result<()> {
+    anyhow::bail!("The system trash is only supported on Windows and macOS")
+}